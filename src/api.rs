@@ -0,0 +1,305 @@
+use once_cell::sync::Lazy;
+use std::collections::HashMap;
+
+const GLICOL_API: &str = include_str!("../glicol/js/src/glicol-api.json");
+
+pub static NODE_DOCS: Lazy<HashMap<String, DocEntry>> =
+    Lazy::new(|| serde_json::from_str(GLICOL_API).unwrap());
+
+#[derive(serde::Deserialize)]
+pub struct DocEntry {
+    description: Option<String>,
+    parameters: Option<Vec<Parameter>>,
+    input: Option<String>,
+    output: Option<String>,
+    example: Option<String>,
+}
+
+/// What a parameter accepts, as far as the docs tell.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum ParameterKind {
+    Number,
+    /// A number or a `~reference` to a signal.
+    Modulable,
+    Sample,
+    /// Any number of notes, rests and references, like in `seq`.
+    Pattern,
+    Any,
+}
+
+#[derive(Debug, Clone, PartialEq, serde::Deserialize)]
+#[serde(from = "serde_json::Value")]
+pub struct Parameter {
+    pub name: String,
+    pub kind: ParameterKind,
+    /// The value used when the argument is left out, like `440.0` or `\\guitar`.
+    pub default: Option<String>,
+    /// The values the docs say are accepted, like `0` to `1`.
+    pub range: Option<(f64, f64)>,
+    pub description: Option<String>,
+}
+
+impl Parameter {
+    /// Parameters are listed either as `{"name": default}` or as plain strings. The default can
+    /// also be a description, or a default wrapped like `Modulable(440.0)`.
+    fn from_value(value: &serde_json::Value) -> Self {
+        match value {
+            serde_json::Value::Object(map) if map.len() == 1 => {
+                let (name, value) = map.iter().next().unwrap();
+
+                let default = default_of(value);
+
+                let description = match value {
+                    serde_json::Value::String(text) if default.is_none() && !text.is_empty() => {
+                        Some(text.clone())
+                    }
+                    _ => None,
+                };
+
+                Self {
+                    name: name.clone(),
+                    kind: ParameterKind::of(value),
+                    range: description.as_deref().and_then(range_of),
+                    default,
+                    description,
+                }
+            }
+            serde_json::Value::String(name) => Self {
+                name: name.clone(),
+                kind: ParameterKind::of(value),
+                default: None,
+                range: None,
+                description: None,
+            },
+            value => Self {
+                name: value.to_string(),
+                kind: ParameterKind::Any,
+                default: None,
+                range: None,
+                description: None,
+            },
+        }
+    }
+
+    /// The kind of value, and its range if there is one, like `a number from 0 to 1`.
+    pub fn type_description(&self) -> String {
+        match self.range {
+            Some((min, max)) => format!("{} from {} to {}", self.kind.describe(), min, max),
+            None => self.kind.describe().to_string(),
+        }
+    }
+}
+
+impl From<serde_json::Value> for Parameter {
+    fn from(value: serde_json::Value) -> Self {
+        Self::from_value(&value)
+    }
+}
+
+fn default_of(value: &serde_json::Value) -> Option<String> {
+    let text = match value {
+        serde_json::Value::Number(number) => return Some(number.to_string()),
+        serde_json::Value::String(text) => text.trim(),
+        _ => return None,
+    };
+
+    if text.parse::<f64>().is_ok() || text.starts_with('\\') {
+        return Some(text.to_string());
+    }
+
+    // Like `Modulable(440.0)`.
+    let (_, wrapped) = text.strip_suffix(')')?.split_once('(')?;
+
+    Some(wrapped.to_string()).filter(|wrapped| wrapped.parse::<f64>().is_ok())
+}
+
+/// Finds ranges like `0-1`, `0.0 ~ 1.0`, `0..1` or `from 20 to 20000` in a description.
+fn range_of(description: &str) -> Option<(f64, f64)> {
+    let words = description
+        .split(|c: char| {
+            c.is_whitespace() || c == ',' || c == '(' || c == ')' || c == '[' || c == ']'
+        })
+        .flat_map(|word| {
+            match word
+                .split_once(['-', '~'])
+                .or_else(|| word.split_once(".."))
+            {
+                Some((min, max)) if !min.is_empty() && !max.is_empty() => vec![min, "to", max],
+                _ => vec![word],
+            }
+        })
+        .filter(|word| !word.is_empty())
+        .collect::<Vec<_>>();
+
+    words.windows(3).find_map(|window| match window {
+        [min, "to" | "-" | "~" | ".." | "and", max] => Some((min.parse().ok()?, max.parse().ok()?)),
+        _ => None,
+    })
+}
+
+impl ParameterKind {
+    pub fn describe(&self) -> &'static str {
+        match self {
+            ParameterKind::Number => "a number",
+            ParameterKind::Modulable => "a number or a reference",
+            ParameterKind::Sample => "a sample",
+            ParameterKind::Pattern => "notes, rests and references",
+            ParameterKind::Any => "anything",
+        }
+    }
+
+    /// Only the forms of the docs are trusted: a number, a `\\sample` or a `Modulable(..)`
+    /// default, or a parameter listed as just `"pattern"`. Descriptions are free text, so a
+    /// parameter that only has one is left as `Any`.
+    fn of(value: &serde_json::Value) -> Self {
+        let text = match value {
+            serde_json::Value::Number(_) => return ParameterKind::Number,
+            serde_json::Value::String(text) => text.trim(),
+            _ => return ParameterKind::Any,
+        };
+
+        if text.starts_with('\\') {
+            ParameterKind::Sample
+        } else if text.starts_with("Modulable(") && text.ends_with(')') {
+            ParameterKind::Modulable
+        } else if text == "pattern" {
+            ParameterKind::Pattern
+        } else if text.parse::<f64>().is_ok() {
+            ParameterKind::Number
+        } else {
+            ParameterKind::Any
+        }
+    }
+}
+
+/// Nodes that take any number of arguments.
+const VARIADIC_NODES: &[&str] = &["seq", "choose", "mix", "arrange"];
+
+/// Whether a node takes any number of arguments instead of one per parameter.
+pub fn is_variadic(node: &str, parameters: &[Parameter]) -> bool {
+    VARIADIC_NODES.contains(&node)
+        || parameters
+            .iter()
+            .any(|parameter| parameter.kind == ParameterKind::Pattern)
+}
+
+fn table_cell(text: &str) -> String {
+    text.replace('|', "\\|").replace('\n', " ")
+}
+
+impl DocEntry {
+    pub fn parameters(&self) -> &[Parameter] {
+        self.parameters.as_deref().unwrap_or_default()
+    }
+
+    /// The first line of the description, for short listings.
+    pub fn summary(&self) -> Option<&str> {
+        self.description
+            .as_deref()
+            .and_then(|description| description.lines().next())
+            .filter(|summary| !summary.is_empty())
+    }
+
+    /// Whether the node makes a signal on its own, rather than processing the signal of the chain.
+    pub fn is_source(&self) -> bool {
+        self.input
+            .as_deref()
+            .map(str::trim)
+            .is_none_or(|input| input.is_empty() || input.eq_ignore_ascii_case("none"))
+    }
+
+    pub fn to_markdown(&self) -> String {
+        self.to_markdown_with(None)
+    }
+
+    /// The docs, with the row of the `active` parameter highlighted in the parameter table.
+    pub fn to_markdown_with(&self, active: Option<usize>) -> String {
+        let mut result = String::new();
+        if let Some(description) = self
+            .description
+            .as_ref()
+            .filter(|description| !description.is_empty())
+        {
+            result.push_str(&format!("## Description\n{}\n", description));
+        }
+
+        if let Some(input) = self.input.as_ref().filter(|input| !input.is_empty()) {
+            result.push_str(&format!("## Input\n{}\n", input));
+        }
+
+        if let Some(output) = self.output.as_ref().filter(|output| !output.is_empty()) {
+            result.push_str(&format!("## Output\n{}\n", output));
+        }
+
+        if let Some(example) = self.example.as_ref().filter(|example| !example.is_empty()) {
+            result.push_str(&format!("## Example\n```glicol\n{}\n```\n", example));
+        }
+
+        if !self.parameters().is_empty() {
+            result.push_str("## Parameters\n| Name | Default | Type | Description |\n");
+            result.push_str("| --- | --- | --- | --- |\n");
+
+            for (i, parameter) in self.parameters().iter().enumerate() {
+                let name = if Some(i) == active {
+                    format!("**→ `{}`**", parameter.name)
+                } else {
+                    format!("`{}`", parameter.name)
+                };
+
+                let default = parameter
+                    .default
+                    .as_ref()
+                    .map(|default| format!("`{}`", default))
+                    .unwrap_or_default();
+
+                result.push_str(&format!(
+                    "| {} | {} | {} | {} |\n",
+                    name,
+                    table_cell(&default),
+                    table_cell(&parameter.type_description()),
+                    table_cell(parameter.description.as_deref().unwrap_or_default())
+                ));
+            }
+        }
+
+        result
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::api::{Parameter, ParameterKind};
+
+    #[test]
+    fn test_parameters() {
+        let parameter = |json: &str| Parameter::from_value(&serde_json::from_str(json).unwrap());
+
+        assert_eq!(
+            parameter(r#"{"freq": "Modulable(440.0)"}"#),
+            Parameter {
+                name: "freq".to_string(),
+                kind: ParameterKind::Modulable,
+                default: Some("440.0".to_string()),
+                range: None,
+                description: None,
+            }
+        );
+        assert_eq!(
+            parameter(r#"{"mix": "dry/wet balance, 0.0 - 1.0"}"#),
+            Parameter {
+                name: "mix".to_string(),
+                kind: ParameterKind::Any,
+                default: None,
+                range: Some((0.0, 1.0)),
+                description: Some("dry/wet balance, 0.0 - 1.0".to_string()),
+            }
+        );
+        assert_eq!(parameter(r#"{"q": 1.0}"#).default.as_deref(), Some("1.0"));
+        assert_eq!(parameter(r#"{"q": 1.0}"#).kind, ParameterKind::Number);
+        assert_eq!(
+            parameter(r#"{"sample": "\\guitar"}"#).kind,
+            ParameterKind::Sample
+        );
+        assert_eq!(parameter(r#""pattern""#).kind, ParameterKind::Pattern);
+    }
+}
@@ -0,0 +1,144 @@
+use crate::graph::Graph;
+use crate::helpers::{byte_range_to_range, position_to_byte};
+use crate::syntax::references;
+use ropey::RopeSlice;
+use std::ops::Range;
+use tower_lsp::lsp_types::{CallHierarchyItem, Position, SymbolKind, Url};
+
+/// The lines the call hierarchy starts from: the line a reference under the cursor reads, every
+/// line a wildcard matches, or the line whose label is under the cursor, outputs included.
+pub fn prepare(graph: &Graph, rope: RopeSlice, line: usize, col: usize) -> Vec<usize> {
+    let byte = position_to_byte(rope, Position::new(line as u32, col as u32));
+
+    let label = (0..graph.lines.len()).find(|&line| {
+        let label = &graph.lines[line].label.byte_range;
+
+        label.start <= byte && byte <= label.end
+    });
+
+    if let Some(label) = label {
+        return vec![label];
+    }
+
+    graph
+        .lines
+        .iter()
+        .flat_map(|line| line.calls.iter())
+        .flat_map(|call| std::iter::once(&call.name).chain(&call.arguments))
+        .flat_map(references)
+        .find(|reference| reference.byte_range.start <= byte && byte <= reference.byte_range.end)
+        .map(|reference| graph.targets(&reference.text))
+        .unwrap_or_default()
+}
+
+/// The lines that read `line`, each with the ranges of the references that do it.
+pub fn incoming(graph: &Graph, line: usize) -> Vec<(usize, Vec<Range<usize>>)> {
+    group(
+        graph
+            .incoming(line)
+            .into_iter()
+            .map(|(source, edge)| (source, edge.reference.byte_range.clone())),
+    )
+}
+
+/// The lines that `line` reads, through its input or as modulation, each with the ranges of the
+/// references in `line` that do it.
+pub fn outgoing(graph: &Graph, line: usize) -> Vec<(usize, Vec<Range<usize>>)> {
+    group(
+        graph
+            .outgoing(line)
+            .iter()
+            .map(|edge| (edge.target, edge.reference.byte_range.clone())),
+    )
+}
+
+fn group(calls: impl Iterator<Item = (usize, Range<usize>)>) -> Vec<(usize, Vec<Range<usize>>)> {
+    let mut result = Vec::<(usize, Vec<Range<usize>>)>::new();
+
+    for (line, byte_range) in calls {
+        match result.iter_mut().find(|(other, _)| *other == line) {
+            Some((_, ranges)) => ranges.push(byte_range),
+            None => result.push((line, vec![byte_range])),
+        }
+    }
+
+    result
+}
+
+pub fn item(graph: &Graph, rope: RopeSlice, uri: &Url, line: usize) -> CallHierarchyItem {
+    let line = &graph.lines[line];
+
+    let kind = if line.label.text.starts_with('~') {
+        SymbolKind::VARIABLE
+    } else {
+        SymbolKind::EVENT
+    };
+
+    let detail = line
+        .calls
+        .iter()
+        .map(|call| rope.byte_slice(call.byte_range()).to_string())
+        .collect::<Vec<_>>()
+        .join(" >> ");
+
+    CallHierarchyItem {
+        name: line.label.text.clone(),
+        kind,
+        tags: None,
+        detail: Some(detail),
+        uri: uri.clone(),
+        range: byte_range_to_range(rope, line.byte_range.clone()),
+        selection_range: byte_range_to_range(rope, line.label.byte_range.clone()),
+        data: None,
+    }
+}
+
+/// Finds the line of an item given back by the client, by its position or else by its name, in
+/// case the document changed in between.
+pub fn line_of(graph: &Graph, rope: RopeSlice, item: &CallHierarchyItem) -> Option<usize> {
+    let labels = || graph.lines.iter().map(|line| &line.label).enumerate();
+
+    labels()
+        .find(|(_, label)| {
+            label.text == item.name
+                && byte_range_to_range(rope, label.byte_range.clone()) == item.selection_range
+        })
+        .or_else(|| labels().find(|(_, label)| label.text == item.name))
+        .map(|(line, _)| line)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::{incoming, outgoing, prepare};
+    use crate::graph::Graph;
+    use crate::helpers::parse;
+
+    #[test]
+    fn test_call_hierarchy() {
+        let source_code = r#"
+~lfo: sin 0.3 >> mul 200
+~kick: seq 60 >> sp \kick1
+~snare: seq _ 60 >> sp \snare1
+~drums: mix ~kick ~snare >> lpf ~lfo 1
+out: mix ~drums >> mul ~lfo
+"#;
+
+        let (tree, rope) = parse(source_code);
+
+        let graph = Graph::new(&tree, rope.slice(..));
+
+        assert_eq!(prepare(&graph, rope.slice(..), 4, 13), vec![1]);
+        assert_eq!(prepare(&graph, rope.slice(..), 5, 1), vec![4]);
+
+        let lines = |calls: Vec<(usize, Vec<std::ops::Range<usize>>)>| {
+            calls
+                .into_iter()
+                .map(|(line, ranges)| (line, ranges.len()))
+                .collect::<Vec<_>>()
+        };
+
+        assert_eq!(lines(incoming(&graph, 0)), vec![(3, 1), (4, 1)]);
+        assert_eq!(lines(outgoing(&graph, 3)), vec![(1, 1), (2, 1), (0, 1)]);
+        assert_eq!(lines(outgoing(&graph, 4)), vec![(3, 1), (0, 1)]);
+    }
+}
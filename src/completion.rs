@@ -0,0 +1,325 @@
+use crate::api::{DocEntry, NODE_DOCS};
+use crate::samples::SampleLibrary;
+use crate::workspace::{self, WorkspaceIndex};
+use ropey::RopeSlice;
+use std::collections::HashSet;
+use tower_lsp::lsp_types::{
+    CompletionItem, CompletionItemKind, CompletionTextEdit, Documentation, InsertTextFormat,
+    MarkupContent, MarkupKind, Position as LspPosition, Range, TextEdit, Url,
+};
+use tree_sitter::Tree;
+
+/// Nodes that start a chain, in case the docs don't say they take no input.
+const SOURCE_NODES: &[&str] = &[
+    "sin", "saw", "squ", "tri", "noise", "imp", "seq", "speed", "choose", "mix", "arrange",
+    "constsig",
+];
+
+/// Where in a chain the cursor is, which decides what to suggest first.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Position {
+    /// Right after `label:`, where a source goes.
+    ChainStart,
+    /// Right after `>>`, where a processor goes.
+    AfterPipe,
+    /// After a `~`, which starts at column `start`.
+    Reference { start: usize },
+    /// After a `\`, which starts at column `start`.
+    Sample { start: usize },
+}
+
+/// Finds out what is being typed at the given position from the text of the line, which is
+/// often not valid yet while typing.
+pub fn position(rope: RopeSlice, line: usize, col: usize) -> Option<Position> {
+    let before = line_before(rope, line, col);
+
+    if before.contains("//") {
+        return None;
+    }
+
+    let name = before.trim_end_matches(|c: char| c.is_alphanumeric() || c == '_' || c == '.');
+    let start = name.chars().count().checked_sub(1);
+
+    if let (Some(start), Some(sigil)) = (start, name.chars().last()) {
+        match sigil {
+            '~' => return Some(Position::Reference { start }),
+            '\\' => return Some(Position::Sample { start }),
+            _ => {}
+        }
+    }
+
+    // Leave out the word being typed.
+    let before = before.trim_end_matches(|c: char| c.is_alphanumeric() || c == '_');
+
+    let before = before.trim_end();
+
+    if before.ends_with(">>") {
+        Some(Position::AfterPipe)
+    } else if before.ends_with(':') {
+        Some(Position::ChainStart)
+    } else {
+        None
+    }
+}
+
+/// Every node of the docs, with the ones that fit the position ranked first.
+pub fn node_completions(position: Position) -> Vec<CompletionItem> {
+    let mut items = NODE_DOCS
+        .iter()
+        .map(|(name, entry)| {
+            let preferred = match position {
+                Position::ChainStart => is_source(name, entry),
+                _ => !is_source(name, entry),
+            };
+
+            CompletionItem {
+                label: name.clone(),
+                kind: Some(CompletionItemKind::FUNCTION),
+                detail: entry.summary().map(str::to_string),
+                documentation: Some(Documentation::MarkupContent(MarkupContent {
+                    kind: MarkupKind::Markdown,
+                    value: entry.to_markdown(),
+                })),
+                sort_text: Some(format!("{}{}", if preferred { 0 } else { 1 }, name)),
+                insert_text: Some(snippet(name, entry)),
+                insert_text_format: Some(InsertTextFormat::SNIPPET),
+                ..CompletionItem::default()
+            }
+        })
+        .collect::<Vec<_>>();
+
+    items.sort_by(|a, b| a.sort_text.cmp(&b.sort_text));
+
+    items
+}
+
+/// The references defined in the document, except on the line being edited, and then the ones
+/// from the rest of the workspace, with their chain.
+pub fn reference_completions(
+    tree: &Tree,
+    rope: RopeSlice,
+    uri: &Url,
+    workspace: &WorkspaceIndex,
+    line: usize,
+    (start, col): (usize, usize),
+) -> Vec<CompletionItem> {
+    let document = workspace::labels(tree, rope)
+        .into_iter()
+        .filter(|label| label.range.start.line as usize != line)
+        .map(|label| (None, label));
+
+    let mut others = workspace
+        .labels()
+        .filter(|(other, _)| *other != uri)
+        .map(|(other, label)| (Some(other), label.clone()))
+        .collect::<Vec<_>>();
+
+    others.sort_by(|(a, a_label), (b, b_label)| {
+        (a_label.name.as_str(), a.map(Url::as_str))
+            .cmp(&(b_label.name.as_str(), b.map(Url::as_str)))
+    });
+
+    let mut seen = HashSet::new();
+
+    document
+        .chain(others)
+        .filter(|(_, label)| label.name.starts_with('~'))
+        .filter(|(_, label)| seen.insert(label.name.clone()))
+        .map(|(other, label)| CompletionItem {
+            label: label.name.clone(),
+            kind: Some(CompletionItemKind::VARIABLE),
+            detail: Some(label.chain),
+            documentation: other
+                .map(|other| Documentation::String(format!("Defined in {}", file_name(other)))),
+            sort_text: Some(format!(
+                "{}{}",
+                if other.is_none() { 0 } else { 1 },
+                label.name
+            )),
+            filter_text: Some(label.name.clone()),
+            text_edit: Some(replace(line, (start, col), label.name)),
+            ..CompletionItem::default()
+        })
+        .collect()
+}
+
+/// Every sample of the library, with the duration and channels of the file it plays.
+pub fn sample_completions(
+    samples: &SampleLibrary,
+    line: usize,
+    (start, col): (usize, usize),
+) -> Vec<CompletionItem> {
+    let mut names = samples.names().collect::<Vec<_>>();
+
+    names.sort();
+
+    names
+        .into_iter()
+        .map(|name| {
+            let paths = samples.get(name).unwrap_or_default();
+
+            let detail = paths
+                .first()
+                .and_then(|path| samples.header(path))
+                .map(|header| header.summary());
+
+            let documentation = paths
+                .iter()
+                .map(|path| format!("- `{}`", path.display()))
+                .collect::<Vec<_>>()
+                .join("\n");
+
+            CompletionItem {
+                label: format!("\\{}", name),
+                kind: Some(CompletionItemKind::FILE),
+                detail,
+                documentation: Some(Documentation::MarkupContent(MarkupContent {
+                    kind: MarkupKind::Markdown,
+                    value: documentation,
+                })),
+                filter_text: Some(format!("\\{}", name)),
+                text_edit: Some(replace(line, (start, col), format!("\\{}", name))),
+                ..CompletionItem::default()
+            }
+        })
+        .collect()
+}
+
+/// Replaces the sigil and what was typed after it, since editors don't take `~` or `\` as part
+/// of the word.
+fn replace(line: usize, (start, col): (usize, usize), new_text: String) -> CompletionTextEdit {
+    CompletionTextEdit::Edit(TextEdit {
+        range: Range::new(
+            LspPosition::new(line as u32, start as u32),
+            LspPosition::new(line as u32, col as u32),
+        ),
+        new_text,
+    })
+}
+
+fn file_name(uri: &Url) -> String {
+    uri.path_segments()
+        .and_then(|mut segments| segments.next_back())
+        .unwrap_or_else(|| uri.as_str())
+        .to_string()
+}
+
+/// The text of the line up to the cursor.
+fn line_before(rope: RopeSlice, line: usize, col: usize) -> String {
+    if line >= rope.len_lines() {
+        return String::new();
+    }
+
+    rope.line(line).chars().take(col).collect()
+}
+
+fn is_source(name: &str, entry: &DocEntry) -> bool {
+    SOURCE_NODES.contains(&name) || entry.is_source()
+}
+
+/// The node followed by a placeholder for each of its parameters, like `lpf ${1:cutoff} ${2:q}`.
+fn snippet(name: &str, entry: &DocEntry) -> String {
+    std::iter::once(escape(name))
+        .chain(
+            entry
+                .parameters()
+                .iter()
+                .enumerate()
+                .map(|(i, parameter)| format!("${{{}:{}}}", i + 1, escape(&parameter.name))),
+        )
+        .collect::<Vec<_>>()
+        .join(" ")
+}
+
+fn escape(text: &str) -> String {
+    text.chars()
+        .flat_map(|c| match c {
+            '$' | '}' | '\\' => vec!['\\', c],
+            c => vec![c],
+        })
+        .collect()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::{node_completions, position, sample_completions, Position};
+    use crate::helpers::TestDir;
+    use crate::samples::SampleLibrary;
+    use crate::wav::fixture;
+    use ropey::Rope;
+    use tower_lsp::lsp_types::CompletionTextEdit;
+
+    #[test]
+    fn test_position() {
+        let rope = Rope::from_str("~a: sin 440 >> lp\nout: s\nout: ~a >> mul 0.5 // >> \n");
+
+        assert_eq!(position(rope.slice(..), 0, 17), Some(Position::AfterPipe));
+        assert_eq!(position(rope.slice(..), 0, 15), Some(Position::AfterPipe));
+        assert_eq!(position(rope.slice(..), 1, 6), Some(Position::ChainStart));
+        assert_eq!(position(rope.slice(..), 0, 11), None);
+        assert_eq!(position(rope.slice(..), 2, 26), None);
+
+        let rope = Rope::from_str("out: mix ~t.. ~b >> sp \\gu");
+
+        assert_eq!(
+            position(rope.slice(..), 0, 16),
+            Some(Position::Reference { start: 14 })
+        );
+        assert_eq!(
+            position(rope.slice(..), 0, 10),
+            Some(Position::Reference { start: 9 })
+        );
+        assert_eq!(
+            position(rope.slice(..), 0, 26),
+            Some(Position::Sample { start: 23 })
+        );
+    }
+
+    #[test]
+    fn test_node_completions() {
+        let items = node_completions(Position::AfterPipe);
+
+        let lpf = items.iter().find(|item| item.label == "lpf").unwrap();
+        let snippet = lpf.insert_text.as_deref().unwrap();
+        assert!(snippet.starts_with("lpf ${1:") && snippet.contains(" ${2:"));
+
+        let first = |items: &[tower_lsp::lsp_types::CompletionItem], a: &str, b: &str| {
+            items.iter().position(|item| item.label == a)
+                < items.iter().position(|item| item.label == b)
+        };
+
+        assert!(first(&items, "lpf", "sin"));
+        assert!(first(&node_completions(Position::ChainStart), "sin", "lpf"));
+    }
+
+    #[test]
+    fn test_sample_completions() {
+        let dir = TestDir::new("sample-completions");
+
+        dir.write("guitar.wav", fixture(1, 44100, &[0; 44100]));
+        dir.write("broken.wav", []);
+
+        let samples = SampleLibrary::index(std::slice::from_ref(&dir.root));
+
+        let items = sample_completions(&samples, 3, (10, 12));
+
+        assert_eq!(
+            items
+                .iter()
+                .map(|item| (item.label.as_str(), item.detail.as_deref()))
+                .collect::<Vec<_>>(),
+            vec![("\\broken", None), ("\\guitar", Some("1.00 s, mono"))]
+        );
+
+        match &items[1].text_edit {
+            Some(CompletionTextEdit::Edit(edit)) => {
+                assert_eq!(edit.new_text, "\\guitar");
+                assert_eq!(
+                    (edit.range.start.character, edit.range.end.character),
+                    (10, 12)
+                );
+            }
+            edit => panic!("unexpected edit {:?}", edit),
+        }
+    }
+}
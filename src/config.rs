@@ -0,0 +1,34 @@
+use serde::Deserialize;
+use std::collections::HashMap;
+use std::path::PathBuf;
+
+/// The section the client is expected to use for the server settings.
+pub const SECTION: &str = "glicol-language-server";
+
+/// Server settings, taken from the `initializationOptions` and from
+/// `workspace/didChangeConfiguration`.
+#[derive(Debug, Default, Clone, Deserialize)]
+#[serde(rename_all = "camelCase", default)]
+pub struct Config {
+    /// Directories that are searched (recursively) for `.wav` files.
+    pub sample_directories: Vec<PathBuf>,
+    /// Turns the parameter name hints of some nodes on or off, like `{"seq": false}`. Nodes that
+    /// are not listed get them.
+    pub inlay_hints: HashMap<String, bool>,
+}
+
+impl Config {
+    /// Accepts either the settings object itself or one nested under [`SECTION`].
+    pub fn from_value(value: serde_json::Value) -> Option<Self> {
+        let value = match value {
+            serde_json::Value::Object(mut map) if map.contains_key(SECTION) => {
+                map.remove(SECTION).unwrap()
+            }
+            value => value,
+        };
+
+        serde_json::from_value(value)
+            .map_err(|error| log::error!("invalid configuration: {}", error))
+            .ok()
+    }
+}
@@ -0,0 +1,845 @@
+use crate::api::{is_variadic, ParameterKind, NODE_DOCS};
+use crate::engine::DocumentEngine;
+use crate::graph::Graph;
+use crate::helpers::byte_range_to_range;
+use crate::samples::SampleLibrary;
+use crate::syntax::{lines, matches, reference_uses, sample_uses, ArgumentKind, Token};
+use glicol::EngineError;
+use pest::error::{Error, LineColLocation};
+use pest::RuleType;
+use ropey::{Rope, RopeSlice};
+use std::sync::Arc;
+use tower_lsp::lsp_types::*;
+use tree_sitter::{Node, Tree};
+
+const MAX_SUGGESTIONS: usize = 3;
+const MAX_UNEXPECTED_LENGTH: usize = 20;
+
+/// Runs the engine over the document and gathers every diagnostic for it.
+pub async fn collect(
+    uri: &Url,
+    engine: &DocumentEngine,
+    tree: &Tree,
+    rope: &Rope,
+    samples: Arc<SampleLibrary>,
+) -> Vec<Diagnostic> {
+    let parse_result = engine.validate(rope.to_string(), samples.clone()).await;
+
+    let rope = rope.byte_slice(..);
+
+    let mut diagnostics = match parse_result {
+        None => syntax_errors(tree, rope, None),
+        Some(Err(EngineError::ParsingError(error))) => {
+            syntax_errors(tree, rope, Some(parsing_error(&error)))
+        }
+        // The engine stops at the first missing reference or sample, so both are looked for in
+        // the tree, with what the engine ran into as a fallback.
+        Some(result) => {
+            let (reference, sample) = match result {
+                Err(EngineError::NonExistReference(name)) => (Some(name), None),
+                Err(EngineError::NonExsitSample(name)) => (None, Some(name)),
+                _ => (None, None),
+            };
+
+            let mut diagnostics = undefined_references(tree, rope, reference.as_deref());
+            diagnostics.extend(missing_samples(tree, rope, &samples, sample.as_deref()));
+
+            diagnostics
+        }
+    };
+
+    diagnostics.extend(arguments(tree, rope));
+
+    // Half-typed lines would make everything after them look unused.
+    if !tree.root_node().has_error() {
+        let graph = Graph::new(tree, rope);
+
+        diagnostics.extend(unused(&graph, rope));
+        diagnostics.extend(cycles(&graph, uri, rope));
+        diagnostics.extend(outputs(&graph, uri, rope));
+    }
+
+    diagnostics
+}
+
+pub fn parsing_error<R: RuleType>(error: &Error<R>) -> Diagnostic {
+    let (start, end) = match &error.line_col {
+        LineColLocation::Pos((line, col)) => {
+            let pos = Position {
+                line: *line as u32 - 1,
+                character: *col as u32 - 1,
+            };
+            (pos, pos)
+        }
+        LineColLocation::Span((line1, col1), (line2, col2)) => (
+            Position {
+                line: *line1 as u32 - 1,
+                character: *col1 as u32 - 1,
+            },
+            Position {
+                line: *line2 as u32 - 1,
+                character: *col2 as u32 - 1,
+            },
+        ),
+    };
+
+    Diagnostic {
+        range: Range { start, end },
+        severity: Some(DiagnosticSeverity::ERROR),
+        code: Some(NumberOrString::String(error.line().to_string())),
+        code_description: None,
+        source: Some("glicol engine".to_string()),
+        message: error.variant.message().to_string(),
+        related_information: None,
+        tags: None,
+        data: None,
+    }
+}
+
+/// Every syntax error in the document.
+///
+/// Pest stops at the first error, while the tree marks all of them, so the errors in the tree are
+/// added to `parsing_error` unless they are on the same spot. When pest is happy there are no
+/// syntax errors, and `parsing_error` is `None` only if the engine didn't get to run.
+pub fn syntax_errors(
+    tree: &Tree,
+    rope: RopeSlice,
+    parsing_error: Option<Diagnostic>,
+) -> Vec<Diagnostic> {
+    let mut errors = vec![];
+    collect_syntax_errors(tree.root_node(), rope, &mut errors);
+
+    if let Some(parsing_error) = parsing_error {
+        errors.retain(|error| !same_spot(error, &parsing_error));
+        errors.push(parsing_error);
+    }
+
+    errors.sort_by_key(|error| (error.range.start.line, error.range.start.character));
+
+    errors
+}
+
+fn collect_syntax_errors(node: Node, rope: RopeSlice, errors: &mut Vec<Diagnostic>) {
+    if !node.has_error() {
+        return;
+    }
+
+    let message = if node.is_missing() {
+        format!("missing `{}`", node.kind())
+    } else if node.is_error() {
+        let text = rope.byte_slice(node.byte_range()).to_string();
+        let text = text.trim();
+
+        if text.is_empty() || text.chars().count() > MAX_UNEXPECTED_LENGTH || text.contains('\n') {
+            "syntax error".to_string()
+        } else {
+            format!("unexpected `{}`", text)
+        }
+    } else {
+        let mut cursor = node.walk();
+
+        for child in node.children(&mut cursor) {
+            collect_syntax_errors(child, rope, errors);
+        }
+
+        return;
+    };
+
+    errors.push(Diagnostic {
+        range: byte_range_to_range(rope, node.byte_range()),
+        severity: Some(DiagnosticSeverity::ERROR),
+        code: None,
+        code_description: None,
+        source: Some("tree-sitter".to_string()),
+        message,
+        related_information: None,
+        tags: None,
+        data: None,
+    });
+}
+
+fn same_spot(a: &Diagnostic, b: &Diagnostic) -> bool {
+    let overlap = a.range.start <= b.range.end && b.range.start <= a.range.end;
+
+    overlap || a.range.start.line == b.range.start.line
+}
+
+/// One diagnostic for every use of a reference that no line defines.
+///
+/// The engine only reports the first missing reference it runs into, which is `name` if it ran
+/// into one. The tree is used to find the rest of them and to place the diagnostics on the actual
+/// tokens.
+pub fn undefined_references(tree: &Tree, rope: RopeSlice, name: Option<&str>) -> Vec<Diagnostic> {
+    let graph = Graph::new(tree, rope);
+
+    let labels = graph
+        .lines
+        .iter()
+        .map(|line| line.label.text.as_str())
+        .filter(|label| label.starts_with('~'))
+        .collect::<Vec<_>>();
+
+    let uses = reference_uses(tree, rope);
+
+    let mut undefined = uses
+        .iter()
+        .filter(|reference| !labels.iter().any(|label| matches(&reference.text, label)))
+        .collect::<Vec<_>>();
+
+    if let (true, Some(name)) = (undefined.is_empty(), name) {
+        undefined = uses
+            .iter()
+            .filter(|reference| reference.text == name)
+            .collect();
+    }
+
+    undefined
+        .into_iter()
+        .map(|reference| Diagnostic {
+            range: byte_range_to_range(rope, reference.byte_range.clone()),
+            severity: Some(DiagnosticSeverity::ERROR),
+            code: None,
+            code_description: None,
+            source: Some("glicol engine".to_string()),
+            message: undefined_reference_message(&reference.text, &labels),
+            related_information: None,
+            tags: None,
+            data: None,
+        })
+        .collect()
+}
+
+/// One diagnostic for every `\sample` that isn't in the sample library.
+///
+/// The engine is loaded with the same library, so `name` (the first one it ran into, if any) should
+/// always be among them. It's only used on its own if the tree doesn't agree.
+///
+/// An empty library means there is nothing to check against (by default no sample directories are
+/// configured), so nothing is reported then.
+pub fn missing_samples(
+    tree: &Tree,
+    rope: RopeSlice,
+    samples: &SampleLibrary,
+    name: Option<&str>,
+) -> Vec<Diagnostic> {
+    if samples.is_empty() {
+        return vec![];
+    }
+
+    let uses = sample_uses(tree, rope);
+
+    let mut missing = uses
+        .iter()
+        .filter(|sample| !samples.contains(&sample.text))
+        .collect::<Vec<_>>();
+
+    if let (true, Some(name)) = (missing.is_empty(), name) {
+        let name = name.strip_prefix('\\').unwrap_or(name);
+
+        missing = uses
+            .iter()
+            .filter(|sample| sample.text[1..] == *name)
+            .collect();
+    }
+
+    missing
+        .into_iter()
+        .map(|sample| Diagnostic {
+            range: byte_range_to_range(rope, sample.byte_range.clone()),
+            severity: Some(DiagnosticSeverity::ERROR),
+            code: None,
+            code_description: None,
+            source: Some("glicol engine".to_string()),
+            message: format!(
+                "no sample named `{}` in the sample directories",
+                sample.text
+            ),
+            related_information: None,
+            tags: None,
+            data: None,
+        })
+        .collect()
+}
+
+/// Checks the arguments of every node call against the parameters in the docs: too many, too
+/// few, or of the wrong kind. Lines with syntax errors are left alone.
+///
+/// The kind of a parameter is only a hint from the docs, so a mismatch is just a warning.
+pub fn arguments(tree: &Tree, rope: RopeSlice) -> Vec<Diagnostic> {
+    let mut diagnostics = vec![];
+
+    let mut push = |token: &Token, severity: DiagnosticSeverity, message: String| {
+        diagnostics.push(Diagnostic {
+            range: byte_range_to_range(rope, token.byte_range.clone()),
+            severity: Some(severity),
+            code: None,
+            code_description: None,
+            source: Some("glicol lsp".to_string()),
+            message,
+            related_information: None,
+            tags: None,
+            data: None,
+        })
+    };
+
+    for line in lines(tree, rope) {
+        if line.has_error {
+            continue;
+        }
+
+        for call in &line.calls {
+            let node = call.name.text.as_str();
+
+            let parameters = match NODE_DOCS.get(node) {
+                Some(entry) => entry.parameters(),
+                None => continue,
+            };
+
+            if is_variadic(node, parameters) {
+                continue;
+            }
+
+            for (argument, parameter) in call.arguments.iter().zip(parameters) {
+                let expected = match (parameter.kind, argument.argument_kind()) {
+                    (ParameterKind::Number, ArgumentKind::Reference | ArgumentKind::Sample) => {
+                        "a number"
+                    }
+                    (ParameterKind::Modulable, ArgumentKind::Sample) => "a number or a reference",
+                    (ParameterKind::Sample, ArgumentKind::Number | ArgumentKind::Reference) => {
+                        "a sample"
+                    }
+                    _ => continue,
+                };
+
+                push(
+                    argument,
+                    DiagnosticSeverity::WARNING,
+                    format!(
+                        "`{}` of `{}` expects {}, found `{}`",
+                        parameter.name, node, expected, argument.text
+                    ),
+                );
+            }
+
+            let names = parameters
+                .iter()
+                .map(|parameter| format!("`{}`", parameter.name))
+                .collect::<Vec<_>>();
+
+            for argument in call.arguments.iter().skip(parameters.len()) {
+                let message = if names.is_empty() {
+                    format!("`{}` takes no arguments", node)
+                } else {
+                    format!(
+                        "`{}` takes {} argument(s): {}",
+                        node,
+                        names.len(),
+                        names.join(", ")
+                    )
+                };
+
+                push(argument, DiagnosticSeverity::ERROR, message);
+            }
+
+            if call.arguments.len() < parameters.len() {
+                push(
+                    &call.name,
+                    DiagnosticSeverity::ERROR,
+                    format!(
+                        "`{}` is missing {}",
+                        node,
+                        names[call.arguments.len()..].join(", ")
+                    ),
+                );
+            }
+        }
+    }
+
+    diagnostics
+}
+
+/// Warns about the lines that never make it to an output, tagged so editors dim them: either
+/// nothing reads them, or only other dead lines do.
+pub fn unused(graph: &Graph, rope: RopeSlice) -> Vec<Diagnostic> {
+    // Without an output everything would be dead, that's reported on its own.
+    if graph.outputs().is_empty() {
+        return vec![];
+    }
+
+    let reachable = graph.reachable();
+
+    (0..graph.lines.len())
+        .filter(|&line| !reachable[line])
+        .map(|line| {
+            let label = &graph.lines[line].label.text;
+
+            let message = if graph.incoming(line).is_empty() {
+                format!("`{}` is never used", label)
+            } else {
+                format!("`{}` is only used by lines that are never played", label)
+            };
+
+            Diagnostic {
+                range: byte_range_to_range(rope, graph.lines[line].byte_range.clone()),
+                severity: Some(DiagnosticSeverity::WARNING),
+                code: None,
+                code_description: None,
+                source: Some("glicol lsp".to_string()),
+                message,
+                related_information: None,
+                tags: Some(vec![DiagnosticTag::UNNECESSARY]),
+                data: None,
+            }
+        })
+        .collect()
+}
+
+/// Reports every loop in the graph once, on the label of its first line, with each reference that
+/// takes part in it as related information.
+pub fn cycles(graph: &Graph, uri: &Url, rope: RopeSlice) -> Vec<Diagnostic> {
+    graph
+        .cycles()
+        .into_iter()
+        .map(|cycle| {
+            let label = |line: usize| graph.lines[line].label.text.as_str();
+
+            let (start, _) = cycle[0];
+
+            let path = std::iter::once(start)
+                .chain(cycle.iter().map(|(_, edge)| edge.target))
+                .map(|line| format!("`{}`", label(line)))
+                .collect::<Vec<_>>();
+
+            let related_information = cycle
+                .iter()
+                .map(|(source, edge)| DiagnosticRelatedInformation {
+                    location: Location {
+                        uri: uri.clone(),
+                        range: byte_range_to_range(rope, edge.reference.byte_range.clone()),
+                    },
+                    message: format!("`{}` reads `{}`", label(*source), label(edge.target)),
+                })
+                .collect();
+
+            Diagnostic {
+                range: byte_range_to_range(rope, graph.lines[start].label.byte_range.clone()),
+                severity: Some(DiagnosticSeverity::ERROR),
+                code: None,
+                code_description: None,
+                source: Some("glicol lsp".to_string()),
+                message: format!("reference cycle: {}", path.join(" -> ")),
+                related_information: Some(related_information),
+                tags: None,
+                data: None,
+            }
+        })
+        .collect()
+}
+
+/// Warns when there is no output line, when there are several, and when a label is defined more
+/// than once. In the last two cases every label after the first one gets the diagnostic.
+pub fn outputs(graph: &Graph, uri: &Url, rope: RopeSlice) -> Vec<Diagnostic> {
+    let mut diagnostics = vec![];
+
+    let location = |line: usize| Location {
+        uri: uri.clone(),
+        range: byte_range_to_range(rope, graph.lines[line].label.byte_range.clone()),
+    };
+
+    let mut warn = |line: usize, message: String, related: Vec<(usize, String)>| {
+        diagnostics.push(Diagnostic {
+            range: location(line).range,
+            severity: Some(DiagnosticSeverity::WARNING),
+            code: None,
+            code_description: None,
+            source: Some("glicol lsp".to_string()),
+            message,
+            related_information: (!related.is_empty()).then(|| {
+                related
+                    .into_iter()
+                    .map(|(line, message)| DiagnosticRelatedInformation {
+                        location: location(line),
+                        message,
+                    })
+                    .collect()
+            }),
+            tags: None,
+            data: None,
+        })
+    };
+
+    let label = |line: usize| graph.lines[line].label.text.as_str();
+
+    let outputs = graph.outputs();
+
+    if outputs.is_empty() && !graph.lines.is_empty() {
+        warn(
+            0,
+            "there is no output line, nothing will be played".to_string(),
+            vec![],
+        );
+    }
+
+    let mut first_output = None;
+
+    for &output in &outputs {
+        match first_output {
+            None => first_output = Some(output),
+            Some(first) if label(first) != label(output) => warn(
+                output,
+                format!(
+                    "`{}` is a second output; `{}` is already one",
+                    label(output),
+                    label(first)
+                ),
+                vec![(first, "the other output".to_string())],
+            ),
+            Some(_) => {}
+        }
+    }
+
+    for line in 0..graph.lines.len() {
+        let first = (0..line).find(|&other| label(other) == label(line));
+
+        if let Some(first) = first {
+            warn(
+                line,
+                format!("`{}` is already defined", label(line)),
+                vec![(first, "first defined here".to_string())],
+            );
+        }
+    }
+
+    diagnostics
+}
+
+fn undefined_reference_message(name: &str, labels: &[&str]) -> String {
+    let threshold = (name.chars().count() / 2).max(2);
+
+    let mut candidates = labels
+        .iter()
+        .map(|label| (edit_distance(name, label), *label))
+        .filter(|(distance, _)| *distance <= threshold)
+        .collect::<Vec<_>>();
+
+    // Duplicated labels would be suggested twice.
+    candidates.sort();
+    candidates.dedup();
+
+    let suggestions = candidates
+        .into_iter()
+        .take(MAX_SUGGESTIONS)
+        .map(|(_, label)| format!("`{}`", label))
+        .collect::<Vec<_>>();
+
+    if suggestions.is_empty() {
+        format!("undefined reference `{}`", name)
+    } else {
+        format!(
+            "undefined reference `{}`, did you mean {}?",
+            name,
+            suggestions.join(", ")
+        )
+    }
+}
+
+fn edit_distance(a: &str, b: &str) -> usize {
+    let b = b.chars().collect::<Vec<_>>();
+    let mut row = (0..=b.len()).collect::<Vec<_>>();
+
+    for (i, ca) in a.chars().enumerate() {
+        let mut diagonal = row[0];
+        row[0] = i + 1;
+
+        for (j, cb) in b.iter().enumerate() {
+            let substitution = diagonal + usize::from(ca != *cb);
+            diagonal = row[j + 1];
+            row[j + 1] = substitution.min(row[j] + 1).min(row[j + 1] + 1);
+        }
+    }
+
+    row[b.len()]
+}
+
+#[cfg(test)]
+mod tests {
+    use super::{
+        arguments, collect, missing_samples, outputs, syntax_errors, undefined_references, unused,
+    };
+    use crate::api::{ParameterKind, NODE_DOCS};
+    use crate::engine::DocumentEngine;
+    use crate::graph::Graph;
+    use crate::helpers::{parse, TestDir};
+    use crate::samples::SampleLibrary;
+    use std::sync::Arc;
+    use tower_lsp::lsp_types::{Diagnostic, DiagnosticSeverity, Position, Range, Url};
+
+    #[test]
+    fn test_undefined_references() {
+        let source_code = r#"
+~drum: seq 60 >> sp \kick1
+~bass: saw 50 >> mul ~drums
+out: mix ~drums ~bass
+"#;
+
+        let (tree, rope) = parse(source_code);
+
+        let diagnostics = undefined_references(&tree, rope.slice(..), Some("~drums"));
+
+        assert_eq!(
+            diagnostics
+                .iter()
+                .map(|diagnostic| diagnostic.range.start.line)
+                .collect::<Vec<_>>(),
+            vec![2, 3]
+        );
+
+        assert_eq!(
+            diagnostics[0].message,
+            "undefined reference `~drums`, did you mean `~drum`?"
+        );
+    }
+
+    #[test]
+    fn test_missing_samples() {
+        let source_code = r#"
+~t1: seq 60 >> sp \guitar
+~t2: seq 60 >> sp \guitar >> mul 0.5
+"#;
+
+        let (tree, rope) = parse(source_code);
+
+        let dir = TestDir::new("missing-samples");
+
+        dir.write("kick1.wav", []);
+
+        let samples = SampleLibrary::index(std::slice::from_ref(&dir.root));
+
+        let diagnostics = missing_samples(&tree, rope.slice(..), &samples, Some("\\guitar"));
+
+        assert_eq!(
+            diagnostics
+                .iter()
+                .map(|diagnostic| (diagnostic.range.start, diagnostic.range.end))
+                .collect::<Vec<_>>(),
+            vec![
+                (Position::new(1, 18), Position::new(1, 25)),
+                (Position::new(2, 18), Position::new(2, 25)),
+            ]
+        );
+    }
+
+    #[test]
+    fn test_missing_samples_without_library() {
+        let source_code = "~t1: seq 60 >> sp \\guitar\n";
+
+        let (tree, rope) = parse(source_code);
+
+        let diagnostics = missing_samples(
+            &tree,
+            rope.slice(..),
+            &SampleLibrary::default(),
+            Some("\\guitar"),
+        );
+
+        assert!(diagnostics.is_empty());
+    }
+
+    #[tokio::test]
+    async fn test_collect_without_library() {
+        let source_code = "~lead: seq 60 >> sp \\guitar\nout: mix ~lead ~pad\n";
+
+        let (tree, rope) = parse(source_code);
+
+        let samples = Arc::new(SampleLibrary::default());
+        let engine = DocumentEngine::default();
+        let uri = Url::parse("file:///set.glicol").unwrap();
+
+        let diagnostics = collect(&uri, &engine, &tree, &rope, samples).await;
+
+        assert_eq!(
+            diagnostics
+                .iter()
+                .map(|diagnostic| diagnostic.message.as_str())
+                .collect::<Vec<_>>(),
+            vec!["undefined reference `~pad`"]
+        );
+    }
+
+    #[test]
+    fn test_syntax_errors() {
+        let source_code = r#"
+~a: sin 440 >>
+~b: saw 50 >> mul 0.5
+~c: ~b >> >> mul 0.5
+"#;
+
+        let (tree, rope) = parse(source_code);
+
+        let lines = |diagnostics: Vec<Diagnostic>| {
+            diagnostics
+                .into_iter()
+                .map(|diagnostic| diagnostic.range.start.line)
+                .collect::<Vec<_>>()
+        };
+
+        let errors = syntax_errors(&tree, rope.slice(..), None);
+
+        assert!(lines(errors.clone()).contains(&1));
+        assert!(lines(errors).contains(&3));
+
+        let parsing_error = Diagnostic::new_simple(
+            Range::new(Position::new(1, 11), Position::new(1, 11)),
+            "expected node".to_string(),
+        );
+
+        let errors = syntax_errors(&tree, rope.slice(..), Some(parsing_error.clone()));
+
+        assert_eq!(
+            errors
+                .iter()
+                .filter(|error| error.range.start.line == 1)
+                .count(),
+            1
+        );
+        assert!(errors.contains(&parsing_error));
+        assert!(lines(errors).contains(&3));
+    }
+
+    #[test]
+    fn test_arguments() {
+        let source_code = r#"
+~a: sin 440 220
+~b: seq 60 _ 61 _ 62 >> sp \guitar
+"#;
+
+        let (tree, rope) = parse(source_code);
+
+        let diagnostics = arguments(&tree, rope.slice(..));
+
+        assert_eq!(diagnostics.len(), 1);
+        assert_eq!(
+            diagnostics[0].range,
+            Range::new(Position::new(1, 12), Position::new(1, 15))
+        );
+    }
+
+    #[test]
+    fn test_arguments_with_api() {
+        let source_code = r#"
+~amp: sin 0.2 >> mul 0.3 >> add 0.5
+~cut: sin 0.1 >> mul 300 >> add 600
+~drums: seq 60 _60 >> sp \guitar
+out: saw 50 >> lpf ~cut 1.0 >> mul ~amp
+"#;
+
+        let (tree, rope) = parse(source_code);
+
+        let diagnostics = arguments(&tree, rope.slice(..));
+
+        assert!(diagnostics.is_empty(), "{:?}", diagnostics);
+
+        let kinds = |node: &str| {
+            NODE_DOCS[node]
+                .parameters()
+                .iter()
+                .map(|parameter| parameter.kind)
+                .collect::<Vec<_>>()
+        };
+
+        assert_eq!(kinds("mul"), vec![ParameterKind::Modulable]);
+        assert_eq!(
+            kinds("lpf"),
+            vec![ParameterKind::Modulable, ParameterKind::Number]
+        );
+        assert_eq!(kinds("sp"), vec![ParameterKind::Sample]);
+
+        let source_code = r#"
+~q: sin 0.2 >> mul 0.5 >> add 1.0
+~drums: seq 60 >> sp 42
+out: saw 50 >> lpf 300 ~q
+"#;
+
+        let (tree, rope) = parse(source_code);
+
+        let diagnostics = arguments(&tree, rope.slice(..));
+
+        let name = |node: &str, index: usize| &NODE_DOCS[node].parameters()[index].name;
+
+        assert_eq!(
+            diagnostics
+                .iter()
+                .map(|diagnostic| (diagnostic.severity, diagnostic.message.clone()))
+                .collect::<Vec<_>>(),
+            vec![
+                (
+                    Some(DiagnosticSeverity::WARNING),
+                    format!("`{}` of `sp` expects a sample, found `42`", name("sp", 0))
+                ),
+                (
+                    Some(DiagnosticSeverity::WARNING),
+                    format!("`{}` of `lpf` expects a number, found `~q`", name("lpf", 1))
+                ),
+            ]
+        );
+    }
+
+    #[test]
+    fn test_unused() {
+        let source_code = r#"
+~mod: sin 0.3
+~lfo: sin 0.1
+~t1: saw 60 >> mul ~mod
+~t2: saw 90 >> mul ~lfo
+out: mix ~t1 >> mul 0.5
+"#;
+
+        let (tree, rope) = parse(source_code);
+
+        let diagnostics = unused(&Graph::new(&tree, rope.slice(..)), rope.slice(..));
+
+        assert_eq!(
+            diagnostics
+                .iter()
+                .map(|diagnostic| diagnostic.message.as_str())
+                .collect::<Vec<_>>(),
+            vec![
+                "`~lfo` is only used by lines that are never played",
+                "`~t2` is never used"
+            ]
+        );
+    }
+
+    #[test]
+    fn test_outputs() {
+        let uri = Url::parse("file:///set.glicol").unwrap();
+
+        let messages = |source_code: &str| {
+            let (tree, rope) = parse(source_code);
+
+            outputs(&Graph::new(&tree, rope.slice(..)), &uri, rope.slice(..))
+                .into_iter()
+                .map(|diagnostic| (diagnostic.range.start.line, diagnostic.message))
+                .collect::<Vec<_>>()
+        };
+
+        assert_eq!(
+            messages("~a: sin 440\n"),
+            vec![(
+                0,
+                "there is no output line, nothing will be played".to_string()
+            )]
+        );
+
+        assert_eq!(
+            messages("~a: sin 440\n~a: sin 220\nout: ~a\no: ~a\n"),
+            vec![
+                (
+                    3,
+                    "`o` is a second output; `out` is already one".to_string()
+                ),
+                (1, "`~a` is already defined".to_string()),
+            ]
+        );
+    }
+}
@@ -0,0 +1,95 @@
+use crate::api::NODE_DOCS;
+use crate::helpers::find_node_for_point;
+use crate::references::references;
+use crate::syntax::{lines, matches, reference_at};
+use ropey::RopeSlice;
+use std::ops::Range;
+use tower_lsp::lsp_types::DocumentHighlightKind;
+use tree_sitter::Tree;
+
+/// With the cursor on a reference, its labels are highlighted as writes and its uses as reads.
+/// With the cursor on a node name, every call of that node is highlighted.
+pub fn document_highlight(
+    tree: &Tree,
+    rope: RopeSlice,
+    line: usize,
+    col: usize,
+) -> Vec<(Range<usize>, DocumentHighlightKind)> {
+    if let Some(symbol) = reference_at(tree, rope, line, col) {
+        let mut result = lines(tree, rope)
+            .into_iter()
+            .map(|line| line.label)
+            .filter(|label| label.text.starts_with('~') && matches(&symbol.text, &label.text))
+            .map(|label| (label.byte_range, DocumentHighlightKind::WRITE))
+            .chain(
+                references(tree, rope, line, col, false)
+                    .into_iter()
+                    .map(|byte_range| (byte_range, DocumentHighlightKind::READ)),
+            )
+            .collect::<Vec<_>>();
+
+        result.sort_by_key(|(byte_range, _)| byte_range.start);
+
+        return result;
+    }
+
+    let mut cursor = tree.walk();
+
+    if !find_node_for_point(&mut cursor, rope, line, col) {
+        return vec![];
+    }
+
+    let name = rope.byte_slice(cursor.node().byte_range()).to_string();
+
+    if !NODE_DOCS.contains_key(&name) {
+        return vec![];
+    }
+
+    lines(tree, rope)
+        .into_iter()
+        .flat_map(|line| line.calls)
+        .filter(|call| call.name.text == name)
+        .map(|call| (call.name.byte_range, DocumentHighlightKind::TEXT))
+        .collect()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::document_highlight;
+    use crate::helpers::parse;
+    use tower_lsp::lsp_types::DocumentHighlightKind;
+
+    #[test]
+    fn test_document_highlight() {
+        let source_code = r#"
+~mod2: sin 0.3
+~u2: saw 60 >> mul ~mod2 >> lpf 400.0 1.0
+out: ~u2 >> lpf 1000.0 1.0
+"#;
+
+        let (tree, rope) = parse(source_code);
+
+        let highlights = |line, col| {
+            document_highlight(&tree, rope.slice(..), line, col)
+                .into_iter()
+                .map(|(range, kind)| (rope.byte_to_line(range.start), kind))
+                .collect::<Vec<_>>()
+        };
+
+        assert_eq!(
+            highlights(2, 20),
+            vec![
+                (1, DocumentHighlightKind::WRITE),
+                (2, DocumentHighlightKind::READ)
+            ]
+        );
+
+        assert_eq!(
+            highlights(2, 29),
+            vec![
+                (2, DocumentHighlightKind::TEXT),
+                (3, DocumentHighlightKind::TEXT)
+            ]
+        );
+    }
+}
@@ -0,0 +1,109 @@
+use crate::helpers::byte_range_to_range;
+use crate::syntax::{lines, Call};
+use ropey::RopeSlice;
+use tower_lsp::lsp_types::{DocumentSymbol, SymbolKind};
+use tree_sitter::Tree;
+
+/// One symbol per line, named after its label, with the node calls of the chain as children.
+pub fn document_symbols(tree: &Tree, rope: RopeSlice) -> Vec<DocumentSymbol> {
+    lines(tree, rope)
+        .into_iter()
+        .map(|line| {
+            let kind = if line.label.text.starts_with('~') {
+                SymbolKind::VARIABLE
+            } else {
+                SymbolKind::EVENT
+            };
+
+            let children = line
+                .calls
+                .iter()
+                .map(|call| call_symbol(call, rope))
+                .collect();
+
+            #[allow(deprecated)]
+            DocumentSymbol {
+                name: line.label.text,
+                detail: None,
+                kind,
+                tags: None,
+                deprecated: None,
+                range: byte_range_to_range(rope, line.byte_range),
+                selection_range: byte_range_to_range(rope, line.label.byte_range),
+                children: Some(children),
+            }
+        })
+        .collect()
+}
+
+fn call_symbol(call: &Call, rope: RopeSlice) -> DocumentSymbol {
+    let kind = if call.name.text.starts_with('~') {
+        SymbolKind::VARIABLE
+    } else {
+        SymbolKind::FUNCTION
+    };
+
+    let name = std::iter::once(&call.name)
+        .chain(&call.arguments)
+        .map(|token| token.text.as_str())
+        .collect::<Vec<_>>()
+        .join(" ");
+
+    #[allow(deprecated)]
+    DocumentSymbol {
+        name,
+        detail: None,
+        kind,
+        tags: None,
+        deprecated: None,
+        range: byte_range_to_range(rope, call.byte_range()),
+        selection_range: byte_range_to_range(rope, call.name.byte_range.clone()),
+        children: None,
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::document_symbols;
+    use crate::helpers::parse;
+    use tower_lsp::lsp_types::{Position, Range};
+
+    #[test]
+    fn test_document_symbols() {
+        let source_code = r#"
+~t1: speed 0.25 >> seq 60 _ _ 69 >> sp \guitar
+out: ~t1 >> mul 0.5
+"#;
+
+        let (tree, rope) = parse(source_code);
+
+        let symbols = document_symbols(&tree, rope.slice(..));
+
+        assert_eq!(
+            symbols
+                .iter()
+                .map(|symbol| symbol.name.as_str())
+                .collect::<Vec<_>>(),
+            vec!["~t1", "out"]
+        );
+
+        let children = symbols[0].children.as_ref().unwrap();
+
+        assert_eq!(
+            children
+                .iter()
+                .map(|symbol| symbol.name.as_str())
+                .collect::<Vec<_>>(),
+            vec!["speed 0.25", "seq 60 _ _ 69", "sp \\guitar"]
+        );
+
+        assert_eq!(
+            children[1].range,
+            Range::new(Position::new(1, 19), Position::new(1, 32))
+        );
+        assert_eq!(
+            children[1].selection_range,
+            Range::new(Position::new(1, 19), Position::new(1, 22))
+        );
+    }
+}
@@ -0,0 +1,74 @@
+use crate::samples::SampleLibrary;
+use glicol::{Engine, EngineError};
+use std::panic::{self, AssertUnwindSafe};
+use std::sync::{Arc, Mutex};
+
+pub const BLOCK_SIZE: usize = 128;
+
+/// A glicol engine that lives as long as the document it validates, so every change goes through
+/// `update_with_code` and the engine only rebuilds the parts of the graph that changed.
+///
+/// The engine is only built on the first validation, on the blocking thread pool.
+#[derive(Clone, Default)]
+pub struct DocumentEngine {
+    engine: Arc<Mutex<Option<Engine<BLOCK_SIZE>>>>,
+}
+
+impl DocumentEngine {
+    /// Drops the engine so that the next validation starts a fresh one, e.g. because the sample
+    /// library changed.
+    ///
+    /// It waits for a running validation to finish, so it's meant for the blocking thread pool.
+    pub fn reset(&self) {
+        let mut engine = self
+            .engine
+            .lock()
+            .unwrap_or_else(|error| error.into_inner());
+
+        *engine = None;
+    }
+
+    /// Feeds `code` to the engine on the blocking thread pool, building the engine with `samples`
+    /// if there is none yet.
+    ///
+    /// Returns `None` if the engine panicked, in which case it's dropped.
+    pub async fn validate(
+        &self,
+        code: String,
+        samples: Arc<SampleLibrary>,
+    ) -> Option<Result<(), EngineError>> {
+        let engine = self.engine.clone();
+
+        tokio::task::spawn_blocking(move || {
+            let mut guard = engine.lock().unwrap_or_else(|error| error.into_inner());
+
+            let result = panic::catch_unwind(AssertUnwindSafe(|| {
+                let engine = guard.get_or_insert_with(|| new_engine(&samples));
+
+                engine.update_with_code(&code);
+                engine.parse()
+            }));
+
+            if result.is_err() {
+                log::error!("the engine panicked, starting a new one");
+                *guard = None;
+            }
+
+            result.ok()
+        })
+        .await
+        .ok()
+        .flatten()
+    }
+}
+
+fn new_engine(samples: &SampleLibrary) -> Engine<BLOCK_SIZE> {
+    let mut engine = Engine::<BLOCK_SIZE>::new();
+
+    // The data is not needed to validate the code, only the names.
+    for name in samples.names() {
+        engine.add_sample(&format!("\\{}", name), &[], 1, 44100);
+    }
+
+    engine
+}
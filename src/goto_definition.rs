@@ -1,6 +1,10 @@
+use crate::graph::Graph;
 use crate::helpers::find_node_for_point;
+use crate::samples::SampleLibrary;
+use crate::syntax::{reference_at, sample_at};
 use ropey::RopeSlice;
-use std::{collections::HashMap, ops::Range};
+use std::ops::Range;
+use std::path::PathBuf;
 use tree_sitter::Tree;
 
 pub fn goto_definition(
@@ -9,58 +13,64 @@ pub fn goto_definition(
     line: usize,
     col: usize,
 ) -> Option<Range<usize>> {
-    let root_node = tree.root_node();
+    let graph = Graph::new(tree, rope);
+
     let mut cursor = tree.walk();
 
-    if !cursor.goto_first_child() {
+    if !find_node_for_point(&mut cursor, rope, line, col) {
         return None;
     }
 
-    let mut definitions = HashMap::<String, Range<usize>>::new();
-
-    loop {
-        let node = cursor.node();
-
-        if node.kind() != "line" {
-            if !cursor.goto_next_sibling() {
-                break;
-            }
+    let name = rope.byte_slice(cursor.node().byte_range()).to_string();
 
-            continue;
-        }
-
-        if !cursor.goto_first_child() {
-            break;
-        }
+    // With duplicated labels, the last one wins.
+    graph
+        .targets(&name)
+        .last()
+        .map(|&target| graph.lines[target].label.byte_range.clone())
+}
 
-        let reference_node = cursor.node();
+/// With the cursor on a wildcard like `~t..`, the range of the wildcard and the labels of every
+/// line it matches.
+pub fn wildcard_definitions(
+    tree: &Tree,
+    rope: RopeSlice,
+    line: usize,
+    col: usize,
+) -> Option<(Range<usize>, Vec<Range<usize>>)> {
+    let symbol =
+        reference_at(tree, rope, line, col).filter(|symbol| symbol.text.ends_with(".."))?;
 
-        definitions.insert(
-            rope.byte_slice(reference_node.byte_range()).to_string(),
-            reference_node.byte_range(),
-        );
+    let graph = Graph::new(tree, rope);
 
-        cursor.goto_parent();
+    let labels = graph
+        .targets(&symbol.text)
+        .into_iter()
+        .map(|target| graph.lines[target].label.byte_range.clone())
+        .collect();
 
-        if !cursor.goto_next_sibling() {
-            break;
-        }
-    }
+    Some((symbol.byte_range, labels))
+}
 
-    cursor.reset(root_node);
+/// With the cursor on a sample like `\kick1`, the `.wav` files it refers to, from every sample
+/// directory that has one.
+pub fn sample_definitions(
+    tree: &Tree,
+    rope: RopeSlice,
+    line: usize,
+    col: usize,
+    samples: &SampleLibrary,
+) -> Option<Vec<PathBuf>> {
+    let sample = sample_at(tree, rope, line, col)?;
 
-    if find_node_for_point(&mut cursor, rope, line, col) {
-        definitions
-            .get(&rope.byte_slice(cursor.node().byte_range()).to_string())
-            .cloned()
-    } else {
-        None
-    }
+    samples.get(&sample.text).map(<[PathBuf]>::to_vec)
 }
 
 #[cfg(test)]
 mod tests {
-    use super::goto_definition;
+    use super::{goto_definition, sample_definitions, wildcard_definitions};
+    use crate::helpers::{parse, TestDir};
+    use crate::samples::SampleLibrary;
     use ropey::Rope;
     use tree_sitter::Parser;
 
@@ -132,4 +142,53 @@ out: mix ~t.. ~b.. ~u.. >> mul 1
         let m = goto_definition(&tree, rope.slice(..), 7, 24).unwrap();
         assert_eq!(rope.byte_slice(m).to_string(), "~a");
     }
+
+    #[test]
+    fn test_wildcard_definitions() {
+        let source_code = r#"
+~t1: saw 60
+~b1: saw 90
+~t2: sin 440
+out: mix ~t.. ~b.. >> mul 0.5
+"#;
+
+        let (tree, rope) = parse(source_code);
+
+        let (origin, labels) = wildcard_definitions(&tree, rope.slice(..), 4, 11).unwrap();
+
+        assert_eq!(rope.byte_slice(origin).to_string(), "~t..");
+        assert_eq!(
+            labels
+                .into_iter()
+                .map(|label| rope.byte_slice(label).to_string())
+                .collect::<Vec<_>>(),
+            vec!["~t1", "~t2"]
+        );
+
+        assert!(wildcard_definitions(&tree, rope.slice(..), 1, 1).is_none());
+    }
+
+    #[test]
+    fn test_sample_definitions() {
+        let dir = TestDir::new("sample-definitions");
+
+        dir.write("a/kick1.wav", []);
+        dir.write("b/kick1.wav", []);
+
+        let samples = SampleLibrary::index(&[dir.join("a"), dir.join("b")]);
+
+        let source_code = r#"
+~b: seq 60 >> sp \kick1
+~c: seq 60 >> sp \snare1
+"#;
+
+        let (tree, rope) = parse(source_code);
+
+        assert_eq!(
+            sample_definitions(&tree, rope.slice(..), 1, 18, &samples).unwrap(),
+            vec![dir.join("a/kick1.wav"), dir.join("b/kick1.wav")]
+        );
+        assert!(sample_definitions(&tree, rope.slice(..), 2, 18, &samples).is_none());
+        assert!(sample_definitions(&tree, rope.slice(..), 1, 1, &samples).is_none());
+    }
 }
@@ -0,0 +1,241 @@
+use crate::syntax::{lines, matches, references, Line, Token};
+use ropey::RopeSlice;
+use std::collections::HashSet;
+use tree_sitter::Tree;
+
+const MAX_CYCLES: usize = 100;
+
+/// A reference in the chain of a line, pointing to one of the lines it resolves to.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Edge {
+    pub reference: Token,
+    pub target: usize,
+}
+
+/// How signals flow between the lines of a document: every line has an edge to each line its
+/// references read from. Wildcards like `~t..` get an edge per matching label.
+#[derive(Debug)]
+pub struct Graph {
+    pub lines: Vec<Line>,
+    edges: Vec<Vec<Edge>>,
+}
+
+impl Graph {
+    pub fn new(tree: &Tree, rope: RopeSlice) -> Self {
+        let lines = lines(tree, rope);
+
+        let edges = lines
+            .iter()
+            .map(|line| {
+                line.calls
+                    .iter()
+                    .flat_map(|call| std::iter::once(&call.name).chain(&call.arguments))
+                    .flat_map(references)
+                    .flat_map(|reference| {
+                        targets(&lines, &reference.text)
+                            .into_iter()
+                            .map(|target| Edge {
+                                reference: reference.clone(),
+                                target,
+                            })
+                            .collect::<Vec<_>>()
+                    })
+                    .collect()
+            })
+            .collect();
+
+        Self { lines, edges }
+    }
+
+    /// The lines a reference like `~t1` or `~t..` reads from.
+    pub fn targets(&self, reference: &str) -> Vec<usize> {
+        targets(&self.lines, reference)
+    }
+
+    /// The edges from `line` to the lines it reads.
+    pub fn outgoing(&self, line: usize) -> &[Edge] {
+        &self.edges[line]
+    }
+
+    /// The lines that read `line`, with the edge that does it.
+    pub fn incoming(&self, line: usize) -> Vec<(usize, &Edge)> {
+        self.edges
+            .iter()
+            .enumerate()
+            .flat_map(|(source, edges)| edges.iter().map(move |edge| (source, edge)))
+            .filter(|(_, edge)| edge.target == line)
+            .collect()
+    }
+
+    /// The lines that are played, like `out`.
+    pub fn outputs(&self) -> Vec<usize> {
+        (0..self.lines.len())
+            .filter(|&line| !is_reference(&self.lines[line].label.text))
+            .collect()
+    }
+
+    /// Whether each line is read, directly or not, by an output.
+    pub fn reachable(&self) -> Vec<bool> {
+        self.reachable_from(self.outputs())
+    }
+
+    /// Every loop in the graph, once, as the edges that lead from its first line back to it.
+    ///
+    /// Loops that go through the same lines are the same loop even if they take different
+    /// references, and at most `MAX_CYCLES` are returned since a few lines can make many of them.
+    pub fn cycles(&self) -> Vec<Vec<(usize, &Edge)>> {
+        let reachable = (0..self.lines.len())
+            .map(|line| self.reachable_from(vec![line]))
+            .collect::<Vec<_>>();
+
+        let mut cycles = vec![];
+
+        for start in 0..self.lines.len() {
+            self.loops_from(start, start, &reachable, &mut vec![], &mut cycles);
+        }
+
+        cycles
+    }
+
+    /// Follows the paths from `line` that can still get back to `start`, only through lines after
+    /// it so that each loop is found from its first line.
+    fn loops_from<'a>(
+        &'a self,
+        start: usize,
+        line: usize,
+        reachable: &[Vec<bool>],
+        path: &mut Vec<(usize, &'a Edge)>,
+        cycles: &mut Vec<Vec<(usize, &'a Edge)>>,
+    ) {
+        let mut targets = HashSet::new();
+
+        for edge in self.outgoing(line) {
+            if cycles.len() >= MAX_CYCLES {
+                return;
+            }
+
+            if !targets.insert(edge.target) {
+                continue;
+            }
+
+            if edge.target == start {
+                path.push((line, edge));
+                cycles.push(path.clone());
+                path.pop();
+            } else if edge.target > start
+                && reachable[edge.target][start]
+                && !path
+                    .iter()
+                    .any(|(_, visited)| visited.target == edge.target)
+            {
+                path.push((line, edge));
+                self.loops_from(start, edge.target, reachable, path, cycles);
+                path.pop();
+            }
+        }
+    }
+
+    fn reachable_from(&self, mut pending: Vec<usize>) -> Vec<bool> {
+        let mut reachable = vec![false; self.lines.len()];
+
+        while let Some(line) = pending.pop() {
+            if reachable[line] {
+                continue;
+            }
+
+            reachable[line] = true;
+
+            pending.extend(self.outgoing(line).iter().map(|edge| edge.target));
+        }
+
+        reachable
+    }
+}
+
+fn targets(lines: &[Line], reference: &str) -> Vec<usize> {
+    (0..lines.len())
+        .filter(|&line| is_reference(&lines[line].label.text))
+        .filter(|&line| matches(reference, &lines[line].label.text))
+        .collect()
+}
+
+fn is_reference(label: &str) -> bool {
+    label.starts_with('~')
+}
+
+#[cfg(test)]
+mod tests {
+    use super::Graph;
+    use crate::helpers::parse;
+
+    #[test]
+    fn test_graph() {
+        let source_code = r#"
+~mod: sin 0.3
+~t1: saw 60 >> mul ~mod
+~t2: saw 90
+~dead: sin 1 >> mul ~t2
+out: mix ~t.. >> mul 0.5
+"#;
+
+        let (tree, rope) = parse(source_code);
+
+        let graph = Graph::new(&tree, rope.slice(..));
+
+        assert_eq!(
+            graph
+                .outgoing(4)
+                .iter()
+                .map(|edge| edge.target)
+                .collect::<Vec<_>>(),
+            vec![1, 2]
+        );
+
+        assert_eq!(
+            graph
+                .incoming(2)
+                .into_iter()
+                .map(|(source, _)| source)
+                .collect::<Vec<_>>(),
+            vec![3, 4]
+        );
+
+        assert_eq!(graph.reachable(), vec![true, true, true, false, true]);
+        assert!(graph.cycles().is_empty());
+    }
+
+    #[test]
+    fn test_cycles() {
+        let source_code = r#"
+~a: ~b >> mul 0.5
+~b: ~c >> lpf 300 1
+~c: ~a >> mul ~b
+~d: ~d >> mul 0.5
+out: mix ~a ~d
+"#;
+
+        let (tree, rope) = parse(source_code);
+
+        let graph = Graph::new(&tree, rope.slice(..));
+
+        let cycles = graph
+            .cycles()
+            .into_iter()
+            .map(|cycle| {
+                cycle
+                    .into_iter()
+                    .map(|(source, edge)| (source, edge.target))
+                    .collect::<Vec<_>>()
+            })
+            .collect::<Vec<_>>();
+
+        assert_eq!(
+            cycles,
+            vec![
+                vec![(0, 1), (1, 2), (2, 0)],
+                vec![(1, 2), (2, 1)],
+                vec![(3, 3)]
+            ]
+        );
+    }
+}
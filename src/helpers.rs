@@ -1,4 +1,6 @@
 use ropey::RopeSlice;
+use std::ops;
+use tower_lsp::lsp_types::{Position, Range};
 use tree_sitter::{Node, TreeCursor};
 
 pub fn find_node_for_point(
@@ -9,7 +11,7 @@ pub fn find_node_for_point(
 ) -> bool {
     let mut result = false;
 
-    let query_byte_index = rope.char_to_byte(rope.line_to_char(line) + col);
+    let query_byte_index = position_to_byte(rope, Position::new(line as u32, col as u32));
 
     loop {
         let current_byte_range = cursor.node().byte_range();
@@ -57,3 +59,92 @@ pub fn traverse_rec(mut cursor: TreeCursor, f: impl Fn(&Node)) {
         }
     }
 }
+
+pub fn byte_to_position(rope: RopeSlice, byte: usize) -> Position {
+    let line = rope.byte_to_line(byte);
+    let line_char = rope.line_to_char(line);
+    let character = rope.byte_to_char(byte) - line_char;
+
+    Position {
+        line: line as u32,
+        character: character as u32,
+    }
+}
+
+pub fn byte_range_to_range(rope: RopeSlice, byte_range: ops::Range<usize>) -> Range {
+    Range {
+        start: byte_to_position(rope, byte_range.start),
+        end: byte_to_position(rope, byte_range.end),
+    }
+}
+
+/// The byte at a position, clamped to the end of its line and of the document.
+pub fn position_to_byte(rope: RopeSlice, position: Position) -> usize {
+    let line = position.line as usize;
+
+    if line >= rope.len_lines() {
+        return rope.len_bytes();
+    }
+
+    let line_char = rope.line_to_char(line);
+    let line_len = rope.line(line).len_chars();
+
+    rope.char_to_byte(line_char + (position.character as usize).min(line_len))
+}
+
+/// Parses `source` with the glicol grammar, along with its rope.
+#[cfg(test)]
+pub fn parse(source: &str) -> (tree_sitter::Tree, ropey::Rope) {
+    let mut parser = tree_sitter::Parser::new();
+
+    parser
+        .set_language(tree_sitter_glicol::language())
+        .expect("Error loading grammar");
+
+    (
+        parser.parse(source, None).unwrap(),
+        ropey::Rope::from_str(source),
+    )
+}
+
+/// A directory under the temporary directory for the files of a test, removed when dropped so
+/// that a failing test doesn't leave it behind.
+#[cfg(test)]
+pub struct TestDir {
+    pub root: std::path::PathBuf,
+}
+
+#[cfg(test)]
+impl TestDir {
+    /// Creates an empty `glicol-lsp-test-{name}-{pid}` directory, replacing what a previous run
+    /// left.
+    pub fn new(name: &str) -> Self {
+        let root =
+            std::env::temp_dir().join(format!("glicol-lsp-test-{}-{}", name, std::process::id()));
+
+        let _ = std::fs::remove_dir_all(&root);
+
+        std::fs::create_dir_all(&root).unwrap();
+
+        Self { root }
+    }
+
+    pub fn join(&self, path: &str) -> std::path::PathBuf {
+        self.root.join(path)
+    }
+
+    /// Writes a file at `path` inside the directory, creating the directories on the way.
+    pub fn write(&self, path: &str, contents: impl AsRef<[u8]>) {
+        let path = self.join(path);
+
+        std::fs::create_dir_all(path.parent().unwrap()).unwrap();
+        std::fs::write(path, contents).unwrap();
+    }
+}
+
+#[cfg(test)]
+impl Drop for TestDir {
+    fn drop(&mut self) {
+        let _ = std::fs::remove_dir_all(&self.root);
+    }
+}
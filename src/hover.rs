@@ -1,29 +1,49 @@
-use crate::helpers::find_node_for_point;
+use crate::api::{is_variadic, NODE_DOCS};
+use crate::graph::Graph;
+use crate::helpers::{find_node_for_point, position_to_byte};
+use crate::music::{nearest_note, note_frequency, note_name, step_start, DEFAULT_BPM};
+use crate::samples::SampleLibrary;
+use crate::summary::summarize;
+use crate::syntax::{
+    argument_at, lines, matches, reference_at, sample_at, ArgumentAt, Call, Line, Token,
+};
 use once_cell::sync::Lazy;
 use ropey::RopeSlice;
 use std::collections::HashMap;
+use std::ops::Range;
+use tower_lsp::lsp_types::Position;
 use tree_sitter::Tree;
 
-const GLICOL_API: &str = include_str!("../glicol/js/src/glicol-api.json");
-
 static NODE_HOVER_DOCS: Lazy<HashMap<String, String>> = Lazy::new(|| {
-    let docs: HashMap<String, DocEntry> = serde_json::from_str(GLICOL_API).unwrap();
-
-    docs.into_iter()
-        .map(|(k, v)| (k, v.to_markdown()))
+    NODE_DOCS
+        .iter()
+        .map(|(k, v)| (k.clone(), v.to_markdown()))
         .collect()
 });
 
-#[derive(serde::Deserialize)]
-pub struct DocEntry {
-    description: Option<String>,
-    parameters: Option<Vec<serde_json::Value>>,
-    input: Option<String>,
-    output: Option<String>,
-    example: Option<String>,
-}
+/// Nodes whose numbers are MIDI notes.
+const NOTE_NODES: &[&str] = &["seq", "choose"];
+
+/// Nodes whose first argument is a frequency, when the docs don't name it as one.
+const FREQUENCY_NODES: &[&str] = &["sin", "saw", "squ", "tri", "imp", "lpf", "hpf"];
 
 pub fn hover(tree: &Tree, rope: RopeSlice, line: usize, col: usize) -> Option<String> {
+    if let Some(wildcard) =
+        reference_at(tree, rope, line, col).filter(|symbol| symbol.text.ends_with(".."))
+    {
+        return Some(wildcard_hover(tree, rope, &wildcard));
+    }
+
+    if let Some(result) = reference_hover(tree, rope, line, col) {
+        return Some(result);
+    }
+
+    if let Some(at) = argument_at(tree, rope, line, col) {
+        let byte = position_to_byte(rope, Position::new(line as u32, col as u32));
+
+        return argument_hover(&at, byte);
+    }
+
     let mut cursor = tree.walk();
 
     if find_node_for_point(&mut cursor, rope, line, col) {
@@ -35,46 +55,368 @@ pub fn hover(tree: &Tree, rope: RopeSlice, line: usize, col: usize) -> Option<St
     }
 }
 
-impl DocEntry {
-    fn to_markdown(&self) -> String {
-        let mut result = String::new();
-        if let Some(description) = self
-            .description
-            .as_ref()
-            .filter(|description| !description.is_empty())
-        {
-            result.push_str(&format!("## Description\n{}\n", description));
-        }
+/// Shows the definition of the reference or label under the cursor, what its chain produces and
+/// the lines that read it.
+fn reference_hover(tree: &Tree, rope: RopeSlice, line: usize, col: usize) -> Option<String> {
+    let byte = position_to_byte(rope, Position::new(line as u32, col as u32));
+
+    let graph = Graph::new(tree, rope);
+
+    let name = graph
+        .lines
+        .iter()
+        .map(|line| &line.label)
+        .find(|label| label.byte_range.start <= byte && byte <= label.byte_range.end)
+        .cloned()
+        .or_else(|| reference_at(tree, rope, line, col))?
+        .text;
+
+    // With duplicated labels, the last one wins.
+    let definition = match graph.targets(&name).last() {
+        Some(&definition) => definition,
+        None => return Some(format!("`{}` is not defined", name)),
+    };
+
+    let line = &graph.lines[definition];
+
+    let mut result = format!(
+        "```glicol\n{}\n```\n{}\n",
+        rope.byte_slice(line.byte_range.clone()).to_string().trim(),
+        summarize(line)
+    );
 
-        if let Some(input) = self.input.as_ref().filter(|input| !input.is_empty()) {
-            result.push_str(&format!("## Input\n{}\n", input));
+    let mut consumers = graph
+        .incoming(definition)
+        .into_iter()
+        .map(|(source, _)| source)
+        .collect::<Vec<_>>();
+
+    consumers.dedup();
+
+    if !consumers.is_empty() {
+        result.push_str("\nUsed by:\n");
+
+        for consumer in consumers {
+            let label = &graph.lines[consumer].label;
+
+            result.push_str(&format!(
+                "- `{}` (line {})\n",
+                label.text,
+                rope.byte_to_line(label.byte_range.start) + 1
+            ));
         }
+    } else if name.starts_with('~') {
+        result.push_str("\nNot used by any line\n");
+    }
+
+    Some(result)
+}
+
+/// The docs of the node an argument is given to, with its parameter highlighted, after what
+/// the number at `byte` means musically if it's a note or a frequency.
+fn argument_hover(at: &ArgumentAt, byte: usize) -> Option<String> {
+    let number = number_hover(at, byte);
+
+    let docs = NODE_DOCS.get(&at.call().name.text).map(|entry| {
+        let parameters = entry.parameters();
+
+        let active = if is_variadic(&at.call().name.text, parameters) {
+            at.argument.min(parameters.len().saturating_sub(1))
+        } else {
+            at.argument
+        };
+
+        let mut result = match parameters.get(active) {
+            Some(parameter) => format!(
+                "Argument {} of `{}`: **{}**\n\n",
+                at.argument + 1,
+                at.call().name.text,
+                parameter.name
+            ),
+            None => format!(
+                "`{}` takes no argument {}\n\n",
+                at.call().name.text,
+                at.argument + 1
+            ),
+        };
+
+        result.push_str(&entry.to_markdown_with(Some(active)));
+
+        result
+    });
 
-        if let Some(output) = self.output.as_ref().filter(|output| !output.is_empty()) {
-            result.push_str(&format!("## Output\n{}\n", output));
+    match (number, docs) {
+        (Some(number), Some(docs)) => Some(format!("{}\n---\n{}", number, docs)),
+        (number, docs) => number.or(docs),
+    }
+}
+
+/// Explains a number musically: notes of `seq` and `choose` get their name and frequency, and
+/// frequencies their nearest note.
+fn number_hover(at: &ArgumentAt, byte: usize) -> Option<String> {
+    let node = at.call().name.text.as_str();
+
+    if NOTE_NODES.contains(&node) {
+        return note_hover(at, byte);
+    }
+
+    let value = at.token().text.parse::<f64>().ok()?;
+
+    let is_frequency = NODE_DOCS
+        .get(node)
+        .and_then(|entry| entry.parameters().get(at.argument))
+        .map(|parameter| parameter.name.to_lowercase())
+        .map(|name| name.contains("freq") || name.contains("cutoff"))
+        .unwrap_or(false)
+        || (at.argument == 0 && FREQUENCY_NODES.contains(&node));
+
+    if !is_frequency {
+        return None;
+    }
+
+    let (note, cents) = nearest_note(value)?;
+
+    let off = match cents {
+        0 => "in tune".to_string(),
+        cents => format!("{:+} cents", cents),
+    };
+
+    Some(format!(
+        "{} Hz is closest to **{}** (MIDI note {}, {:.2} Hz), {}\n",
+        value,
+        note_name(note),
+        note,
+        note_frequency(f64::from(note)),
+        off
+    ))
+}
+
+/// The note at `byte` of a step, which can be split in parts like `_60`, and when it starts if
+/// it's in a `seq`.
+fn note_hover(at: &ArgumentAt, byte: usize) -> Option<String> {
+    let token = at.token();
+    let parts = step_parts(&token.text);
+
+    let offset = byte.saturating_sub(token.byte_range.start);
+    let part = parts
+        .iter()
+        .position(|part| offset < part.end)
+        .or(parts.len().checked_sub(1))?;
+
+    let value = token.text[parts[part].clone()].parse::<f64>().ok()?;
+
+    if value.fract() != 0.0 || !(0.0..=127.0).contains(&value) {
+        return None;
+    }
+
+    let mut result = format!(
+        "MIDI note {} is **{}**, {:.2} Hz\n",
+        value,
+        note_name(value as i32),
+        note_frequency(value)
+    );
+
+    if at.call().name.text == "seq" {
+        let speed = speed_before(&at.line.calls[..at.call]);
+
+        let steps = at.call().arguments.len();
+
+        let step = if parts.len() > 1 {
+            format!(
+                "Step {} of {}, part {} of {}",
+                at.argument + 1,
+                steps,
+                part + 1,
+                parts.len()
+            )
+        } else {
+            format!("Step {} of {}", at.argument + 1, steps)
+        };
+
+        // The parts of a step split it evenly.
+        let start = step_start(at.argument * parts.len() + part, steps * parts.len(), speed);
+
+        result.push_str(&format!(
+            "\n{}, starting {:.3} s into the bar at speed {} ({} BPM)\n",
+            step, start, speed, DEFAULT_BPM
+        ));
+    }
+
+    Some(result)
+}
+
+/// The byte ranges of the parts of a `seq` step: each `_` is a rest of its own, and notes or
+/// references like `60` or `~a` take one part each, so `_60` is a rest and then a note.
+fn step_parts(step: &str) -> Vec<Range<usize>> {
+    let mut parts: Vec<Range<usize>> = vec![];
+
+    for (i, c) in step.char_indices() {
+        let end = i + c.len_utf8();
+
+        match parts.last_mut() {
+            Some(last) if c != '_' && !step[last.clone()].starts_with('_') => last.end = end,
+            _ => parts.push(i..end),
         }
+    }
 
-        if let Some(example) = self.example.as_ref().filter(|example| !example.is_empty()) {
-            result.push_str(&format!("## Example\n```glicol\n{}\n```\n", example));
+    parts
+}
+
+/// A sample under the cursor, found from the tree so that its files can be read without the
+/// document.
+#[derive(Debug, Clone, PartialEq)]
+pub struct SampleHover {
+    pub sample: Token,
+    /// How long a step of the sequence that triggers the sample lasts, in seconds.
+    pub step: Option<f64>,
+}
+
+pub fn sample_hover_at(
+    tree: &Tree,
+    rope: RopeSlice,
+    line: usize,
+    col: usize,
+) -> Option<SampleHover> {
+    let sample = sample_at(tree, rope, line, col)?;
+
+    let step = lines(tree, rope)
+        .into_iter()
+        .find(|line| {
+            line.byte_range.start <= sample.byte_range.start
+                && sample.byte_range.end <= line.byte_range.end
+        })
+        .and_then(|line| step_duration(&line, sample.byte_range.start));
+
+    Some(SampleHover { sample, step })
+}
+
+/// The step length of the last `seq` before `byte` in the chain, given the `speed` before it.
+fn step_duration(line: &Line, byte: usize) -> Option<f64> {
+    let before = line
+        .calls
+        .iter()
+        .take_while(|call| call.name.byte_range.start < byte)
+        .count();
+
+    let calls = &line.calls[..before];
+
+    let seq = calls.iter().rposition(|call| call.name.text == "seq")?;
+    let steps = calls[seq].arguments.len();
+
+    let speed = speed_before(&calls[..seq]);
+
+    (steps > 0).then(|| step_start(1, steps, speed))
+}
+
+/// The speed set by the last `speed` node of `calls`, 1 if there is none.
+fn speed_before(calls: &[Call]) -> f64 {
+    calls
+        .iter()
+        .rev()
+        .find(|call| call.name.text == "speed")
+        .and_then(|call| call.arguments.first())
+        .and_then(|argument| argument.text.parse::<f64>().ok())
+        .filter(|speed| *speed > 0.0)
+        .unwrap_or(1.0)
+}
+
+impl SampleHover {
+    /// Reads the files of the sample, which can take a while for long ones.
+    pub fn to_markdown(&self, samples: &SampleLibrary) -> String {
+        let paths = match samples.get(&self.sample.text) {
+            Some(paths) => paths,
+            None => return format!("`{}` is not in the sample directories", self.sample.text),
+        };
+
+        let path = &paths[0];
+
+        let mut result = format!("**{}**: `{}`\n", self.sample.text, path.display());
+
+        let header = match samples.header(path) {
+            Some(header) => header,
+            None => {
+                result.push_str("\nThe file could not be read as a WAV file\n");
+
+                return result;
+            }
+        };
+
+        let duration = header.duration().as_secs_f64();
+
+        result.push_str(&format!(
+            "\n- Duration: {:.3} s\n- Sample rate: {} Hz\n- Channels: {}\n",
+            duration, header.sample_rate, header.channels
+        ));
+
+        match header.peak(path) {
+            Ok(peak) if peak > 0.0 => result.push_str(&format!(
+                "- Peak: {:.2} ({:.1} dBFS)\n",
+                peak,
+                20.0 * peak.log10()
+            )),
+            Ok(_) => result.push_str("- Peak: silent\n"),
+            Err(error) => log::warn!("can't read {}: {}", path.display(), error),
         }
 
-        if let Some(parameters) = &self.parameters {
+        if let Some(step) = self.step {
+            let fits = if duration <= step {
+                "the sample ends before the next step"
+            } else {
+                "the sample is still playing at the next step"
+            };
+
             result.push_str(&format!(
-                "## Parameters\n```json\n{}\n```\n",
-                serde_json::to_string(parameters).unwrap()
+                "\nA step of the sequence lasts {:.3} s, {}\n",
+                step, fits
             ));
         }
 
+        if paths.len() > 1 {
+            result.push_str("\nOther files with the same name:\n");
+
+            for path in &paths[1..] {
+                result.push_str(&format!("- `{}`\n", path.display()));
+            }
+        }
+
         result
     }
 }
 
+/// Lists the lines a wildcard like `~t..` expands to.
+fn wildcard_hover(tree: &Tree, rope: RopeSlice, wildcard: &Token) -> String {
+    let expansion = lines(tree, rope)
+        .into_iter()
+        .filter(|line| {
+            line.label.text.starts_with('~') && matches(&wildcard.text, &line.label.text)
+        })
+        .map(|line| {
+            format!(
+                "- `{}`",
+                rope.byte_slice(line.byte_range).to_string().trim()
+            )
+        })
+        .collect::<Vec<_>>();
+
+    if expansion.is_empty() {
+        format!("`{}` doesn't match any reference", wildcard.text)
+    } else {
+        format!(
+            "`{}` expands to:\n{}\n",
+            wildcard.text,
+            expansion.join("\n")
+        )
+    }
+}
+
 #[cfg(test)]
 mod tests {
     use ropey::Rope;
     use tree_sitter::Parser;
 
-    use crate::hover::hover;
+    use crate::helpers::parse;
+    use crate::hover::{hover, sample_hover_at};
+    use crate::samples::SampleLibrary;
 
     #[test]
     fn test_parse() {
@@ -93,4 +435,97 @@ mod tests {
 
         dbg!(hover(&tree, rope.slice(..), 1, 5).unwrap());
     }
+
+    #[test]
+    fn test_wildcard_hover() {
+        let source_code = r#"
+~t1: saw 60
+~b1: saw 90
+~t2: sin 440
+out: mix ~t.. ~x.. >> mul 0.5
+"#;
+
+        let (tree, rope) = parse(source_code);
+
+        assert_eq!(
+            hover(&tree, rope.slice(..), 4, 11).unwrap(),
+            "`~t..` expands to:\n- `~t1: saw 60`\n- `~t2: sin 440`\n"
+        );
+        assert_eq!(
+            hover(&tree, rope.slice(..), 4, 15).unwrap(),
+            "`~x..` doesn't match any reference"
+        );
+    }
+
+    #[test]
+    fn test_reference_hover() {
+        let source_code = r#"
+~mod2: sin 0.3
+~u2: saw 60 >> mul ~mod2
+out: ~u2 >> mul ~mod2
+"#;
+
+        let (tree, rope) = parse(source_code);
+
+        assert_eq!(
+            hover(&tree, rope.slice(..), 2, 20).unwrap(),
+            "```glicol\n~mod2: sin 0.3\n```\ncontrol-rate sine at 0.3 Hz\n\n\
+             Used by:\n- `~u2` (line 3)\n- `out` (line 4)\n"
+        );
+        assert!(hover(&tree, rope.slice(..), 3, 1)
+            .unwrap()
+            .starts_with("```glicol\nout: ~u2 >> mul ~mod2\n```\n`~u2` → "));
+    }
+
+    #[test]
+    fn test_number_hover() {
+        let source_code = r#"
+~t1: speed 4.0 >> seq 60 _ _ 69 >> sp \guitar
+out: sin 445 >> mul 0.5
+~t2: seq 60 _60 _ 48
+"#;
+
+        let (tree, rope) = parse(source_code);
+
+        assert!(hover(&tree, rope.slice(..), 1, 29).unwrap().starts_with(
+            "MIDI note 69 is **A4**, 440.00 Hz\n\n\
+             Step 4 of 4, starting 0.375 s into the bar at speed 4 (120 BPM)\n"
+        ));
+        assert!(hover(&tree, rope.slice(..), 2, 10)
+            .unwrap()
+            .starts_with("445 Hz is closest to **A4** (MIDI note 69, 440.00 Hz), +20 cents\n"));
+
+        let mul = hover(&tree, rope.slice(..), 2, 22).unwrap();
+
+        assert!(mul.starts_with("Argument 1 of `mul`: "));
+        assert!(mul.contains("| **→ `"));
+
+        assert!(hover(&tree, rope.slice(..), 3, 14).unwrap().starts_with(
+            "MIDI note 60 is **C4**, 261.63 Hz\n\n\
+             Step 2 of 4, part 2 of 2, starting 0.750 s into the bar at speed 1 (120 BPM)\n"
+        ));
+        assert!(!hover(&tree, rope.slice(..), 3, 12)
+            .unwrap()
+            .starts_with("MIDI note"));
+    }
+
+    #[test]
+    fn test_sample_hover() {
+        let source_code = r#"
+~t1: speed 4.0 >> seq 60 _ _ 69 >> sp \guitar
+"#;
+
+        let (tree, rope) = parse(source_code);
+
+        let sample = sample_hover_at(&tree, rope.slice(..), 1, 40).unwrap();
+
+        assert_eq!(sample.sample.text, "\\guitar");
+        assert_eq!(sample.step, Some(0.125));
+
+        assert_eq!(
+            sample.to_markdown(&SampleLibrary::default()),
+            "`\\guitar` is not in the sample directories"
+        );
+        assert!(sample_hover_at(&tree, rope.slice(..), 1, 20).is_none());
+    }
 }
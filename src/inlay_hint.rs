@@ -0,0 +1,89 @@
+use crate::api::{is_variadic, NODE_DOCS};
+use crate::syntax::lines;
+use ropey::RopeSlice;
+use std::collections::HashMap;
+use std::ops::Range;
+use tree_sitter::Tree;
+
+/// The name of the parameter each argument in `byte_range` goes to, as the byte where the hint
+/// goes and its label, like `cutoff:`. Nodes that take any number of arguments get no hints, and
+/// neither do the ones turned off in `enabled`.
+pub fn inlay_hints(
+    tree: &Tree,
+    rope: RopeSlice,
+    byte_range: Range<usize>,
+    enabled: &HashMap<String, bool>,
+) -> Vec<(usize, String)> {
+    lines(tree, rope)
+        .into_iter()
+        .filter(|line| {
+            line.byte_range.start < byte_range.end && byte_range.start < line.byte_range.end
+        })
+        .flat_map(|line| line.calls)
+        .filter(|call| enabled.get(&call.name.text).copied().unwrap_or(true))
+        .flat_map(|call| {
+            let parameters = NODE_DOCS
+                .get(&call.name.text)
+                .map(|entry| entry.parameters())
+                .unwrap_or_default();
+
+            if is_variadic(&call.name.text, parameters) {
+                return vec![];
+            }
+
+            call.arguments
+                .iter()
+                .zip(parameters)
+                .map(|(argument, parameter)| {
+                    (argument.byte_range.start, format!("{}:", parameter.name))
+                })
+                .collect()
+        })
+        .filter(|(byte, _)| byte_range.contains(byte))
+        .collect()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::inlay_hints;
+    use crate::helpers::{byte_to_position, parse};
+    use std::collections::HashMap;
+
+    #[test]
+    fn test_inlay_hints() {
+        let source_code = r#"
+~a: seq 60 _ 62 >> sp \guitar
+~b: saw 60 >> lpf 400.0 1.0
+out: ~b >> lpf 1000.0 1.0
+"#;
+
+        let (tree, rope) = parse(source_code);
+
+        let positions = |hints: Vec<(usize, String)>| {
+            hints
+                .into_iter()
+                .map(|(byte, _)| byte_to_position(rope.slice(..), byte))
+                .map(|position| (position.line, position.character))
+                .collect::<Vec<_>>()
+        };
+
+        let visible = rope.line_to_byte(2)..rope.line_to_byte(3);
+
+        assert_eq!(
+            positions(inlay_hints(
+                &tree,
+                rope.slice(..),
+                visible.clone(),
+                &HashMap::new()
+            )),
+            vec![(2, 8), (2, 18), (2, 24)]
+        );
+
+        let enabled = HashMap::from([("lpf".to_string(), false)]);
+
+        assert_eq!(
+            positions(inlay_hints(&tree, rope.slice(..), visible, &enabled)),
+            vec![(2, 8)]
+        );
+    }
+}
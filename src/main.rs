@@ -1,38 +1,129 @@
+pub mod api;
+pub mod call_hierarchy;
+pub mod completion;
+pub mod config;
+pub mod diagnostics;
+pub mod document_highlight;
+pub mod document_symbol;
+pub mod engine;
 pub mod goto_definition;
+pub mod graph;
 pub mod helpers;
 pub mod hover;
+pub mod inlay_hint;
+pub mod music;
+pub mod references;
+pub mod rename;
+pub mod samples;
 pub mod semantic_token;
+pub mod signature_help;
+pub mod summary;
+pub mod syntax;
+pub mod wav;
+pub mod workspace;
 
+use config::Config;
 use dashmap::DashMap;
-use glicol::EngineError;
+use engine::DocumentEngine;
 use goto_definition::goto_definition;
-use pest::error::LineColLocation;
-use ropey::{Rope, RopeSlice};
+use graph::Graph;
+use helpers::{byte_range_to_range, byte_to_position, position_to_byte};
+use ropey::Rope;
+use samples::SampleLibrary;
 use semantic_token::{Highlighter, LEGEND_TYPE};
-use std::borrow::Cow;
-use tokio::sync::Mutex;
-use tower_lsp::jsonrpc::Result;
+use std::collections::HashMap;
+use std::sync::Arc;
+use std::time::Duration;
+use tokio::sync::{Mutex, RwLock};
+use tokio::task::JoinHandle;
+use tower_lsp::jsonrpc::{Error, Result};
 use tower_lsp::lsp_types::*;
 use tower_lsp::{Client, LanguageServer, LspService, Server};
 use tree_sitter::{InputEdit, Parser, Point, Tree};
+use workspace::WorkspaceIndex;
+
+/// How long to wait for more changes before computing the diagnostics.
+const DIAGNOSTICS_DELAY: Duration = Duration::from_millis(250);
 
 struct Backend {
     client: Client,
     parser: Mutex<Parser>,
-    documents: DashMap<Url, (Tree, Rope, Mutex<Highlighter>)>,
+    documents: DashMap<Url, Document>,
+    samples: RwLock<Arc<SampleLibrary>>,
+    workspace: RwLock<WorkspaceIndex>,
+    config: RwLock<Config>,
+}
+
+struct Document {
+    tree: Tree,
+    rope: Rope,
+    highlighter: Mutex<Highlighter>,
+    engine: DocumentEngine,
+    version: i32,
+    /// The diagnostics that are pending or running, aborted when a newer version comes in.
+    diagnostics: Option<JoinHandle<()>>,
 }
 
 #[tower_lsp::async_trait]
 impl LanguageServer for Backend {
-    async fn initialize(&self, _: InitializeParams) -> Result<InitializeResult> {
+    async fn initialize(&self, params: InitializeParams) -> Result<InitializeResult> {
+        if let Some(config) = params.initialization_options.and_then(Config::from_value) {
+            self.configure(config).await;
+        }
+
+        #[allow(deprecated)]
+        let folders = match (params.workspace_folders, params.root_uri) {
+            (Some(folders), _) => folders.into_iter().map(|folder| folder.uri).collect(),
+            (None, Some(root)) => vec![root],
+            (None, None) => vec![],
+        };
+
+        *self.workspace.write().await = WorkspaceIndex::new(
+            folders
+                .into_iter()
+                .filter_map(|uri| uri.to_file_path().ok())
+                .collect(),
+        );
+
         Ok(InitializeResult {
             server_info: None,
             capabilities: ServerCapabilities {
-                text_document_sync: Some(TextDocumentSyncCapability::Kind(
-                    TextDocumentSyncKind::INCREMENTAL,
+                text_document_sync: Some(TextDocumentSyncCapability::Options(
+                    TextDocumentSyncOptions {
+                        open_close: Some(true),
+                        change: Some(TextDocumentSyncKind::INCREMENTAL),
+                        save: Some(TextDocumentSyncSaveOptions::Supported(true)),
+                        ..TextDocumentSyncOptions::default()
+                    },
                 )),
                 definition_provider: Some(OneOf::Left(true)),
+                references_provider: Some(OneOf::Left(true)),
+                document_highlight_provider: Some(OneOf::Left(true)),
+                document_symbol_provider: Some(OneOf::Left(true)),
+                call_hierarchy_provider: Some(CallHierarchyServerCapability::Simple(true)),
+                workspace_symbol_provider: Some(OneOf::Left(true)),
+                workspace: Some(WorkspaceServerCapabilities {
+                    workspace_folders: Some(WorkspaceFoldersServerCapabilities {
+                        supported: Some(true),
+                        change_notifications: Some(OneOf::Left(true)),
+                    }),
+                    file_operations: None,
+                }),
+                rename_provider: Some(OneOf::Right(RenameOptions {
+                    prepare_provider: Some(true),
+                    work_done_progress_options: WorkDoneProgressOptions::default(),
+                })),
                 hover_provider: Some(HoverProviderCapability::Simple(true)),
+                inlay_hint_provider: Some(OneOf::Left(true)),
+                signature_help_provider: Some(SignatureHelpOptions {
+                    trigger_characters: Some(vec![" ".to_string()]),
+                    retrigger_characters: Some(vec![" ".to_string()]),
+                    work_done_progress_options: WorkDoneProgressOptions::default(),
+                }),
+                completion_provider: Some(CompletionOptions {
+                    trigger_characters: Some(vec!["~".to_string(), "\\".to_string()]),
+                    ..CompletionOptions::default()
+                }),
                 semantic_tokens_provider: Some(
                     SemanticTokensServerCapabilities::SemanticTokensRegistrationOptions(
                         SemanticTokensRegistrationOptions {
@@ -67,27 +158,112 @@ impl LanguageServer for Backend {
         self.client
             .log_message(MessageType::INFO, "glicol lsp server initialized!")
             .await;
+
+        let watchers = DidChangeWatchedFilesRegistrationOptions {
+            watchers: vec![FileSystemWatcher {
+                glob_pattern: GlobPattern::String("**/*.glicol".to_string()),
+                kind: None,
+            }],
+        };
+
+        let registration = Registration {
+            id: "glicol-files".to_string(),
+            method: "workspace/didChangeWatchedFiles".to_string(),
+            register_options: serde_json::to_value(watchers).ok(),
+        };
+
+        if let Err(error) = self.client.register_capability(vec![registration]).await {
+            log::warn!("can't watch the .glicol files: {}", error);
+        }
+
+        let folders = self.workspace.read().await.folders.clone();
+
+        self.index_folders(folders).await;
     }
 
     async fn shutdown(&self) -> Result<()> {
         Ok(())
     }
 
-    async fn did_open(&self, params: DidOpenTextDocumentParams) {
-        let mut parser = self.parser.lock().await;
+    async fn did_change_configuration(&self, params: DidChangeConfigurationParams) {
+        if let Some(config) = Config::from_value(params.settings) {
+            self.configure(config).await;
+        }
+    }
 
-        let new_tree = parser.parse(&params.text_document.text, None);
+    async fn did_change_workspace_folders(&self, params: DidChangeWorkspaceFoldersParams) {
+        let paths = |folders: Vec<WorkspaceFolder>| {
+            folders
+                .into_iter()
+                .filter_map(|folder| folder.uri.to_file_path().ok())
+                .collect::<Vec<_>>()
+        };
+
+        let removed = paths(params.event.removed);
+        let added = paths(params.event.added);
+
+        let mut workspace = self.workspace.write().await;
+
+        workspace.folders.retain(|folder| !removed.contains(folder));
+        workspace.folders.extend(added.iter().cloned());
+        workspace.retain_folders();
+
+        drop(workspace);
+
+        self.index_folders(added).await;
+    }
+
+    async fn did_change_watched_files(&self, params: DidChangeWatchedFilesParams) {
+        let mut changed = vec![];
+        let mut workspace = self.workspace.write().await;
+
+        for change in params.changes {
+            // Open documents are indexed from the editor instead.
+            if self.documents.contains_key(&change.uri) {
+                continue;
+            }
+
+            if change.typ == FileChangeType::DELETED {
+                workspace.remove(&change.uri);
+            } else {
+                changed.push(change.uri);
+            }
+        }
+
+        drop(workspace);
+
+        self.reload_files(changed).await;
+    }
+
+    async fn did_open(&self, params: DidOpenTextDocumentParams) {
+        let new_tree = self
+            .parser
+            .lock()
+            .await
+            .parse(&params.text_document.text, None);
 
         if let Some(new_tree) = new_tree {
+            let rope = Rope::from_str(&params.text_document.text);
+
+            self.workspace.write().await.update(
+                params.text_document.uri.clone(),
+                workspace::labels(&new_tree, rope.slice(..)),
+            );
+
             self.documents.insert(
-                params.text_document.uri,
-                (
-                    new_tree,
-                    Rope::from_str(&params.text_document.text),
-                    Mutex::new(Default::default()),
-                ),
+                params.text_document.uri.clone(),
+                Document {
+                    tree: new_tree,
+                    rope,
+                    highlighter: Mutex::new(Default::default()),
+                    engine: DocumentEngine::default(),
+                    version: params.text_document.version,
+                    diagnostics: None,
+                },
             );
         }
+
+        self.schedule_diagnostics(&params.text_document.uri).await;
     }
 
     async fn did_change(&self, params: DidChangeTextDocumentParams) {
@@ -95,7 +271,12 @@ impl LanguageServer for Backend {
         let data = self.documents.get_mut(&params.text_document.uri);
 
         if let Some(mut data) = data {
-            let (tree, rope, _) = data.value_mut();
+            let Document {
+                tree,
+                rope,
+                version,
+                ..
+            } = data.value_mut();
 
             for change in params.content_changes {
                 if let Some(range) = change.range {
@@ -140,30 +321,45 @@ impl LanguageServer for Backend {
 
                 *tree = new_tree.unwrap();
 
-                self.client
-                    .log_message(MessageType::INFO, change.text)
-                    .await;
+                log::debug!("change: {:?}", change.text);
             }
 
-            self.diagnostics(
-                &params.text_document.uri,
-                rope.byte_slice(..),
-                params.text_document.version,
-            )
-            .await;
+            *version = params.text_document.version;
+
+            let labels = workspace::labels(tree, rope.slice(..));
+
+            drop(data);
+            drop(parser);
+
+            self.workspace
+                .write()
+                .await
+                .update(params.text_document.uri.clone(), labels);
+
+            self.schedule_diagnostics(&params.text_document.uri).await;
         }
     }
 
-    async fn did_save(&self, _: DidSaveTextDocumentParams) {}
+    async fn did_save(&self, params: DidSaveTextDocumentParams) {
+        self.schedule_diagnostics(&params.text_document.uri).await;
+    }
 
     async fn did_close(&self, params: DidCloseTextDocumentParams) {
-        self.documents.remove(&params.text_document.uri);
+        if let Some((_, document)) = self.documents.remove(&params.text_document.uri) {
+            if let Some(task) = document.diagnostics {
+                task.abort();
+            }
+        }
+
+        self.reload_files(vec![params.text_document.uri]).await;
     }
 
     async fn goto_definition(
         &self,
         params: GotoDefinitionParams,
     ) -> Result<Option<GotoDefinitionResponse>> {
+        let samples = self.samples.read().await.clone();
+
         let data = self
             .documents
             .get(&params.text_document_position_params.text_document.uri);
@@ -174,7 +370,58 @@ impl LanguageServer for Backend {
             return Ok(None);
         };
 
-        let (tree, rope, _) = data.value();
+        let Document { tree, rope, .. } = data.value();
+
+        let uri = &params.text_document_position_params.text_document.uri;
+
+        if let Some(paths) = goto_definition::sample_definitions(
+            tree,
+            rope.byte_slice(..),
+            params.text_document_position_params.position.line as usize,
+            params.text_document_position_params.position.character as usize,
+            &samples,
+        ) {
+            let mut locations = paths
+                .into_iter()
+                .filter_map(|path| Url::from_file_path(path).ok())
+                .map(|uri| Location {
+                    uri,
+                    range: Range::default(),
+                })
+                .collect::<Vec<_>>();
+
+            return Ok(match locations.len() {
+                0 => None,
+                1 => locations.pop().map(GotoDefinitionResponse::Scalar),
+                _ => Some(GotoDefinitionResponse::Array(locations)),
+            });
+        }
+
+        if let Some((origin, labels)) = goto_definition::wildcard_definitions(
+            tree,
+            rope.byte_slice(..),
+            params.text_document_position_params.position.line as usize,
+            params.text_document_position_params.position.character as usize,
+        ) {
+            // The whole wildcard is the origin, not just the word under the cursor.
+            let origin = byte_range_to_range(rope.byte_slice(..), origin);
+
+            return Ok(Some(GotoDefinitionResponse::Link(
+                labels
+                    .into_iter()
+                    .map(|byte_range| {
+                        let range = byte_range_to_range(rope.byte_slice(..), byte_range);
+
+                        LocationLink {
+                            origin_selection_range: Some(origin),
+                            target_uri: uri.clone(),
+                            target_range: range,
+                            target_selection_range: range,
+                        }
+                    })
+                    .collect(),
+            )));
+        }
 
         goto_definition(
             tree,
@@ -183,30 +430,49 @@ impl LanguageServer for Backend {
             params.text_document_position_params.position.character as usize,
         )
         .map(|byte_range| {
-            let byte_to_position = |byte| {
-                let start_line = rope.byte_to_line(byte);
-                let start_line_char = rope.line_to_char(start_line);
-                let start_char = rope.byte_to_char(byte) - start_line_char;
-
-                Position {
-                    line: start_line as u32,
-                    character: start_char as u32,
-                }
-            };
-
             GotoDefinitionResponse::Scalar(Location {
-                uri: params.text_document_position_params.text_document.uri,
-                range: Range {
-                    start: byte_to_position(byte_range.start),
-                    end: byte_to_position(byte_range.end),
-                },
+                uri: uri.clone(),
+                range: byte_range_to_range(rope.byte_slice(..), byte_range),
             })
         })
         .map(Ok)
         .transpose()
     }
 
-    async fn hover(&self, params: HoverParams) -> Result<Option<Hover>> {
+    async fn references(&self, params: ReferenceParams) -> Result<Option<Vec<Location>>> {
+        let uri = params.text_document_position.text_document.uri;
+
+        let data = self.documents.get(&uri);
+
+        let data = if let Some(data) = data {
+            data
+        } else {
+            return Ok(None);
+        };
+
+        let Document { tree, rope, .. } = data.value();
+
+        let locations = references::references(
+            tree,
+            rope.byte_slice(..),
+            params.text_document_position.position.line as usize,
+            params.text_document_position.position.character as usize,
+            params.context.include_declaration,
+        )
+        .into_iter()
+        .map(|byte_range| Location {
+            uri: uri.clone(),
+            range: byte_range_to_range(rope.byte_slice(..), byte_range),
+        })
+        .collect();
+
+        Ok(Some(locations))
+    }
+
+    async fn document_highlight(
+        &self,
+        params: DocumentHighlightParams,
+    ) -> Result<Option<Vec<DocumentHighlight>>> {
         let data = self
             .documents
             .get(&params.text_document_position_params.text_document.uri);
@@ -217,20 +483,350 @@ impl LanguageServer for Backend {
             return Ok(None);
         };
 
-        let (tree, rope, _) = data.value();
+        let Document { tree, rope, .. } = data.value();
 
-        Ok(hover::hover(
+        let highlights = document_highlight::document_highlight(
             tree,
             rope.byte_slice(..),
             params.text_document_position_params.position.line as usize,
             params.text_document_position_params.position.character as usize,
         )
-        .map(|raw| Hover {
-            contents: HoverContents::Scalar(MarkedString::String(raw)),
-            range: None,
+        .into_iter()
+        .map(|(byte_range, kind)| DocumentHighlight {
+            range: byte_range_to_range(rope.byte_slice(..), byte_range),
+            kind: Some(kind),
+        })
+        .collect();
+
+        Ok(Some(highlights))
+    }
+
+    async fn document_symbol(
+        &self,
+        params: DocumentSymbolParams,
+    ) -> Result<Option<DocumentSymbolResponse>> {
+        let data = self.documents.get(&params.text_document.uri);
+
+        let data = if let Some(data) = data {
+            data
+        } else {
+            return Ok(None);
+        };
+
+        let Document { tree, rope, .. } = data.value();
+
+        Ok(Some(DocumentSymbolResponse::Nested(
+            document_symbol::document_symbols(tree, rope.byte_slice(..)),
+        )))
+    }
+
+    async fn symbol(
+        &self,
+        params: WorkspaceSymbolParams,
+    ) -> Result<Option<Vec<SymbolInformation>>> {
+        Ok(Some(self.workspace.read().await.symbols(&params.query)))
+    }
+
+    async fn prepare_call_hierarchy(
+        &self,
+        params: CallHierarchyPrepareParams,
+    ) -> Result<Option<Vec<CallHierarchyItem>>> {
+        let uri = params.text_document_position_params.text_document.uri;
+
+        let data = self.documents.get(&uri);
+
+        let data = if let Some(data) = data {
+            data
+        } else {
+            return Ok(None);
+        };
+
+        let Document { tree, rope, .. } = data.value();
+
+        let graph = Graph::new(tree, rope.byte_slice(..));
+
+        let items = call_hierarchy::prepare(
+            &graph,
+            rope.byte_slice(..),
+            params.text_document_position_params.position.line as usize,
+            params.text_document_position_params.position.character as usize,
+        )
+        .into_iter()
+        .map(|line| call_hierarchy::item(&graph, rope.byte_slice(..), &uri, line))
+        .collect::<Vec<_>>();
+
+        Ok(Some(items).filter(|items| !items.is_empty()))
+    }
+
+    async fn incoming_calls(
+        &self,
+        params: CallHierarchyIncomingCallsParams,
+    ) -> Result<Option<Vec<CallHierarchyIncomingCall>>> {
+        let uri = &params.item.uri;
+
+        let data = self.documents.get(uri);
+
+        let data = if let Some(data) = data {
+            data
+        } else {
+            return Ok(None);
+        };
+
+        let Document { tree, rope, .. } = data.value();
+
+        let graph = Graph::new(tree, rope.byte_slice(..));
+
+        let line = match call_hierarchy::line_of(&graph, rope.byte_slice(..), &params.item) {
+            Some(line) => line,
+            None => return Ok(None),
+        };
+
+        let calls = call_hierarchy::incoming(&graph, line)
+            .into_iter()
+            .map(|(source, byte_ranges)| CallHierarchyIncomingCall {
+                from: call_hierarchy::item(&graph, rope.byte_slice(..), uri, source),
+                from_ranges: byte_ranges
+                    .into_iter()
+                    .map(|byte_range| byte_range_to_range(rope.byte_slice(..), byte_range))
+                    .collect(),
+            })
+            .collect();
+
+        Ok(Some(calls))
+    }
+
+    async fn outgoing_calls(
+        &self,
+        params: CallHierarchyOutgoingCallsParams,
+    ) -> Result<Option<Vec<CallHierarchyOutgoingCall>>> {
+        let uri = &params.item.uri;
+
+        let data = self.documents.get(uri);
+
+        let data = if let Some(data) = data {
+            data
+        } else {
+            return Ok(None);
+        };
+
+        let Document { tree, rope, .. } = data.value();
+
+        let graph = Graph::new(tree, rope.byte_slice(..));
+
+        let line = match call_hierarchy::line_of(&graph, rope.byte_slice(..), &params.item) {
+            Some(line) => line,
+            None => return Ok(None),
+        };
+
+        let calls = call_hierarchy::outgoing(&graph, line)
+            .into_iter()
+            .map(|(target, byte_ranges)| CallHierarchyOutgoingCall {
+                to: call_hierarchy::item(&graph, rope.byte_slice(..), uri, target),
+                from_ranges: byte_ranges
+                    .into_iter()
+                    .map(|byte_range| byte_range_to_range(rope.byte_slice(..), byte_range))
+                    .collect(),
+            })
+            .collect();
+
+        Ok(Some(calls))
+    }
+
+    async fn prepare_rename(
+        &self,
+        params: TextDocumentPositionParams,
+    ) -> Result<Option<PrepareRenameResponse>> {
+        let data = self.documents.get(&params.text_document.uri);
+
+        let data = if let Some(data) = data {
+            data
+        } else {
+            return Ok(None);
+        };
+
+        let Document { tree, rope, .. } = data.value();
+
+        let byte_range = rename::prepare_rename(
+            tree,
+            rope.byte_slice(..),
+            params.position.line as usize,
+            params.position.character as usize,
+        )
+        .map_err(Error::invalid_params)?;
+
+        Ok(byte_range.map(|byte_range| {
+            PrepareRenameResponse::Range(byte_range_to_range(rope.byte_slice(..), byte_range))
         }))
     }
 
+    async fn rename(&self, params: RenameParams) -> Result<Option<WorkspaceEdit>> {
+        let uri = params.text_document_position.text_document.uri;
+
+        let data = self.documents.get(&uri);
+
+        let data = if let Some(data) = data {
+            data
+        } else {
+            return Ok(None);
+        };
+
+        let Document { tree, rope, .. } = data.value();
+
+        let edits = rename::rename(
+            tree,
+            rope.byte_slice(..),
+            params.text_document_position.position.line as usize,
+            params.text_document_position.position.character as usize,
+            &params.new_name,
+        )
+        .map_err(Error::invalid_params)?
+        .into_iter()
+        .map(|byte_range| TextEdit {
+            range: byte_range_to_range(rope.byte_slice(..), byte_range),
+            new_text: params.new_name.clone(),
+        })
+        .collect();
+
+        Ok(Some(WorkspaceEdit::new(HashMap::from([(
+            uri.clone(),
+            edits,
+        )]))))
+    }
+
+    async fn completion(&self, params: CompletionParams) -> Result<Option<CompletionResponse>> {
+        let workspace = self.workspace.read().await;
+        let samples = self.samples.read().await.clone();
+
+        let data = self
+            .documents
+            .get(&params.text_document_position.text_document.uri);
+
+        let data = if let Some(data) = data {
+            data
+        } else {
+            return Ok(None);
+        };
+
+        let Document { tree, rope, .. } = data.value();
+
+        let line = params.text_document_position.position.line as usize;
+        let col = params.text_document_position.position.character as usize;
+
+        let items = match completion::position(rope.byte_slice(..), line, col) {
+            Some(completion::Position::Reference { start }) => completion::reference_completions(
+                tree,
+                rope.byte_slice(..),
+                &params.text_document_position.text_document.uri,
+                &workspace,
+                line,
+                (start, col),
+            ),
+            Some(completion::Position::Sample { start }) => {
+                completion::sample_completions(&samples, line, (start, col))
+            }
+            Some(position) => completion::node_completions(position),
+            None => return Ok(None),
+        };
+
+        Ok(Some(CompletionResponse::Array(items)))
+    }
+
+    async fn signature_help(&self, params: SignatureHelpParams) -> Result<Option<SignatureHelp>> {
+        let data = self
+            .documents
+            .get(&params.text_document_position_params.text_document.uri);
+
+        let data = if let Some(data) = data {
+            data
+        } else {
+            return Ok(None);
+        };
+
+        let Document { tree, rope, .. } = data.value();
+
+        Ok(signature_help::signature_help(
+            tree,
+            rope.byte_slice(..),
+            params.text_document_position_params.position.line as usize,
+            params.text_document_position_params.position.character as usize,
+        ))
+    }
+
+    async fn inlay_hint(&self, params: InlayHintParams) -> Result<Option<Vec<InlayHint>>> {
+        let enabled = self.config.read().await.inlay_hints.clone();
+
+        let data = self.documents.get(&params.text_document.uri);
+
+        let data = if let Some(data) = data {
+            data
+        } else {
+            return Ok(None);
+        };
+
+        let Document { tree, rope, .. } = data.value();
+
+        let byte_range = position_to_byte(rope.byte_slice(..), params.range.start)
+            ..position_to_byte(rope.byte_slice(..), params.range.end);
+
+        let hints = inlay_hint::inlay_hints(tree, rope.byte_slice(..), byte_range, &enabled)
+            .into_iter()
+            .map(|(byte, label)| InlayHint {
+                position: byte_to_position(rope.byte_slice(..), byte),
+                label: InlayHintLabel::String(label),
+                kind: Some(InlayHintKind::PARAMETER),
+                text_edits: None,
+                tooltip: None,
+                padding_left: None,
+                padding_right: Some(true),
+                data: None,
+            })
+            .collect();
+
+        Ok(Some(hints))
+    }
+
+    async fn hover(&self, params: HoverParams) -> Result<Option<Hover>> {
+        let data = self
+            .documents
+            .get(&params.text_document_position_params.text_document.uri);
+
+        let data = if let Some(data) = data {
+            data
+        } else {
+            return Ok(None);
+        };
+
+        let Document { tree, rope, .. } = data.value();
+
+        let line = params.text_document_position_params.position.line as usize;
+        let col = params.text_document_position_params.position.character as usize;
+
+        if let Some(sample) = hover::sample_hover_at(tree, rope.byte_slice(..), line, col) {
+            let range = byte_range_to_range(rope.byte_slice(..), sample.sample.byte_range.clone());
+
+            drop(data);
+
+            let samples = self.samples.read().await.clone();
+
+            // The peak level needs the whole file.
+            let raw = tokio::task::spawn_blocking(move || sample.to_markdown(&samples))
+                .await
+                .map_err(|_| Error::internal_error())?;
+
+            return Ok(Some(Hover {
+                contents: HoverContents::Scalar(MarkedString::String(raw)),
+                range: Some(range),
+            }));
+        }
+
+        Ok(
+            hover::hover(tree, rope.byte_slice(..), line, col).map(|raw| Hover {
+                contents: HoverContents::Scalar(MarkedString::String(raw)),
+                range: None,
+            }),
+        )
+    }
+
     async fn semantic_tokens_full(
         &self,
         params: SemanticTokensParams,
@@ -243,7 +839,9 @@ impl LanguageServer for Backend {
             return Ok(None);
         };
 
-        let (_tree, rope, highlighter) = data.value();
+        let Document {
+            rope, highlighter, ..
+        } = data.value();
 
         let data = highlighter
             .lock()
@@ -268,7 +866,9 @@ impl LanguageServer for Backend {
             return Ok(None);
         };
 
-        let (_tree, rope, highlighter) = data.value();
+        let Document {
+            rope, highlighter, ..
+        } = data.value();
 
         let data = highlighter
             .lock()
@@ -282,78 +882,113 @@ impl LanguageServer for Backend {
     }
 }
 
-const BLOCK_SIZE: usize = 128;
-
 impl Backend {
-    async fn diagnostics<'a>(&self, uri: &Url, rope_slice: RopeSlice<'a>, version: i32) {
-        let parse_result = match std::panic::catch_unwind(|| {
-            // TODO: keep the Engine around
-            let mut engine = glicol::Engine::<BLOCK_SIZE>::new();
-
-            let code: Cow<str> = rope_slice.into();
-            engine.update_with_code(&code);
-            engine.parse()
-        }) {
-            Ok(no_panic) => no_panic,
-            Err(_panic) => {
-                self.client
-                    .publish_diagnostics(uri.clone(), vec![], Some(version))
-                    .await;
-
-                return;
+    async fn configure(&self, config: Config) {
+        let directories = config.sample_directories.clone();
+
+        *self.config.write().await = config;
+
+        let engines = self
+            .documents
+            .iter()
+            .map(|document| document.engine.clone())
+            .collect::<Vec<_>>();
+
+        let samples = tokio::task::spawn_blocking(move || SampleLibrary::index(&directories))
+            .await
+            .unwrap_or_default();
+
+        *self.samples.write().await = Arc::new(samples);
+
+        // The engines are rebuilt with the new library on their next validation. Resetting waits
+        // for the validations that are running.
+        let _ = tokio::task::spawn_blocking(move || {
+            for engine in engines {
+                engine.reset();
             }
-        };
+        })
+        .await;
 
-        if let Err(error) = parse_result {
-            match error {
-                EngineError::ParsingError(error) => {
-                    let (start, end) = match &error.line_col {
-                        LineColLocation::Pos((line, col)) => {
-                            let pos = Position {
-                                line: *line as u32 - 1,
-                                character: *col as u32 - 1,
-                            };
-                            (pos, pos)
-                        }
-                        LineColLocation::Span((line1, col1), (line2, col2)) => (
-                            Position {
-                                line: *line1 as u32 - 1,
-                                character: *col1 as u32 - 1,
-                            },
-                            Position {
-                                line: *line2 as u32 - 1,
-                                character: *col2 as u32 - 1,
-                            },
-                        ),
-                    };
-
-                    self.client
-                        .publish_diagnostics(
-                            uri.clone(),
-                            vec![Diagnostic {
-                                range: Range { start, end },
-                                severity: Some(DiagnosticSeverity::ERROR),
-                                code: Some(NumberOrString::String(error.line().to_string())),
-                                code_description: None,
-                                source: Some("glicol engine".to_string()),
-                                message: error.variant.message().to_string(),
-                                related_information: None,
-                                tags: None,
-                                data: None,
-                            }],
-                            Some(version),
-                        )
-                        .await;
-                }
-                EngineError::NonExistReference(_) => log::error!("unimplemented"),
-                EngineError::NonExsitSample(_) => log::error!("unimplemented"),
+        let uris = self
+            .documents
+            .iter()
+            .map(|document| document.key().clone())
+            .collect::<Vec<_>>();
+
+        for uri in uris {
+            self.schedule_diagnostics(&uri).await;
+        }
+
+        // Fails before the client is initialized, when the hints are asked for anyway.
+        let _ = self.client.inlay_hint_refresh().await;
+    }
+
+    /// Indexes the `.glicol` files under `folders` from disk, except for the open documents.
+    async fn index_folders(&self, folders: Vec<std::path::PathBuf>) {
+        let files = tokio::task::spawn_blocking(move || WorkspaceIndex::scan(&folders))
+            .await
+            .unwrap_or_default();
+
+        let mut workspace = self.workspace.write().await;
+
+        for (uri, labels) in files {
+            if !self.documents.contains_key(&uri) {
+                workspace.update(uri, labels);
+            }
+        }
+    }
+
+    /// Indexes files again from disk, or forgets the ones that are gone or outside the workspace.
+    async fn reload_files(&self, uris: Vec<Url>) {
+        if uris.is_empty() {
+            return;
+        }
+
+        let files = tokio::task::spawn_blocking(move || WorkspaceIndex::read(uris))
+            .await
+            .unwrap_or_default();
+
+        let mut workspace = self.workspace.write().await;
+
+        for (uri, labels) in files {
+            // It may have been opened while it was being read.
+            if !self.documents.contains_key(&uri) {
+                workspace.reload(uri, labels);
             }
-        } else {
-            self.client
-                .publish_diagnostics(uri.clone(), vec![], Some(version))
-                .await;
         }
     }
+
+    /// Computes and publishes the diagnostics of a document in the background, after a short
+    /// delay. Whatever was scheduled before for the same document is dropped.
+    async fn schedule_diagnostics(&self, uri: &Url) {
+        let samples = self.samples.read().await.clone();
+
+        let mut document = match self.documents.get_mut(uri) {
+            Some(document) => document,
+            None => return,
+        };
+
+        if let Some(task) = document.diagnostics.take() {
+            task.abort();
+        }
+
+        let client = self.client.clone();
+        let uri = uri.clone();
+        let tree = document.tree.clone();
+        let rope = document.rope.clone();
+        let engine = document.engine.clone();
+        let version = document.version;
+
+        document.diagnostics = Some(tokio::spawn(async move {
+            tokio::time::sleep(DIAGNOSTICS_DELAY).await;
+
+            let diagnostics = diagnostics::collect(&uri, &engine, &tree, &rope, samples).await;
+
+            client
+                .publish_diagnostics(uri, diagnostics, Some(version))
+                .await;
+        }));
+    }
 }
 
 #[tokio::main]
@@ -373,6 +1008,9 @@ async fn main() {
         client,
         parser: Mutex::new(parser),
         documents: DashMap::new(),
+        samples: RwLock::new(Arc::new(SampleLibrary::default())),
+        workspace: RwLock::new(WorkspaceIndex::default()),
+        config: RwLock::new(Config::default()),
     });
 
     Server::new(stdin, stdout, socket).serve(service).await;
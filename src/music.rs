@@ -0,0 +1,67 @@
+const NOTE_NAMES: [&str; 12] = [
+    "C", "C#", "D", "D#", "E", "F", "F#", "G", "G#", "A", "A#", "B",
+];
+
+/// A4, the reference for the other notes.
+const A4: f64 = 440.0;
+const A4_NOTE: i32 = 69;
+
+/// Glicol's tempo unless it is changed.
+pub const DEFAULT_BPM: f64 = 120.0;
+
+/// The name of a MIDI note, where 60 is `C4` and 69 is `A4`.
+pub fn note_name(note: i32) -> String {
+    format!(
+        "{}{}",
+        NOTE_NAMES[note.rem_euclid(12) as usize],
+        note.div_euclid(12) - 1
+    )
+}
+
+/// The frequency of a MIDI note, in equal temperament.
+pub fn note_frequency(note: f64) -> f64 {
+    A4 * 2f64.powf((note - f64::from(A4_NOTE)) / 12.0)
+}
+
+/// The closest MIDI note to a frequency and how far off it is, in cents.
+pub fn nearest_note(frequency: f64) -> Option<(i32, i32)> {
+    if !frequency.is_finite() || frequency <= 0.0 {
+        return None;
+    }
+
+    let exact = f64::from(A4_NOTE) + 12.0 * (frequency / A4).log2();
+    let note = exact.round();
+
+    Some((note as i32, ((exact - note) * 100.0).round() as i32))
+}
+
+/// When a step of a sequence starts, in seconds from the start of the bar. A bar is four beats,
+/// and `speed` plays it that many times faster.
+pub fn step_start(step: usize, steps: usize, speed: f64) -> f64 {
+    let bar = 4.0 * 60.0 / DEFAULT_BPM;
+
+    bar / speed * step as f64 / steps as f64
+}
+
+#[cfg(test)]
+mod tests {
+    use super::{nearest_note, note_frequency, note_name, step_start};
+
+    #[test]
+    fn test_notes() {
+        assert_eq!(note_name(60), "C4");
+        assert_eq!(note_name(69), "A4");
+        assert_eq!(note_name(70), "A#4");
+        assert_eq!(note_name(0), "C-1");
+
+        assert_eq!(note_frequency(69.0), 440.0);
+        assert!((note_frequency(60.0) - 261.63).abs() < 0.01);
+
+        assert_eq!(nearest_note(440.0), Some((69, 0)));
+        assert_eq!(nearest_note(445.0), Some((69, 20)));
+        assert_eq!(nearest_note(0.0), None);
+
+        assert_eq!(step_start(2, 4, 1.0), 1.0);
+        assert_eq!(step_start(2, 4, 4.0), 0.25);
+    }
+}
@@ -0,0 +1,105 @@
+use crate::syntax::{lines, matches, reference_at, reference_uses};
+use ropey::RopeSlice;
+use std::ops::Range;
+use tree_sitter::Tree;
+
+/// Every use of the reference at the given position, including wildcards like `~t..` that match
+/// it, and its definition if `include_declaration` is set.
+///
+/// With the cursor on a wildcard the uses are the other occurrences of the same wildcard, and the
+/// declarations are all the labels it matches.
+pub fn references(
+    tree: &Tree,
+    rope: RopeSlice,
+    line: usize,
+    col: usize,
+    include_declaration: bool,
+) -> Vec<Range<usize>> {
+    let symbol = match reference_at(tree, rope, line, col) {
+        Some(symbol) => symbol,
+        None => return vec![],
+    };
+
+    let wildcard = symbol.text.ends_with("..");
+
+    let mut result = reference_uses(tree, rope)
+        .into_iter()
+        .filter(|reference| {
+            if wildcard {
+                reference.text == symbol.text
+            } else {
+                matches(&reference.text, &symbol.text)
+            }
+        })
+        .map(|reference| reference.byte_range)
+        .collect::<Vec<_>>();
+
+    if include_declaration {
+        result.extend(
+            lines(tree, rope)
+                .into_iter()
+                .map(|line| line.label)
+                .filter(|label| label.text.starts_with('~'))
+                .filter(|label| matches(&symbol.text, &label.text))
+                .map(|label| label.byte_range),
+        );
+    }
+
+    result.sort_by_key(|byte_range| byte_range.start);
+
+    result
+}
+
+#[cfg(test)]
+mod tests {
+    use super::references;
+    use crate::helpers::parse;
+
+    #[test]
+    fn test_references() {
+        let source_code = r#"
+~mod2: sin 0.3
+~t1: speed 4.0 >> seq 60 ~mod2 >> sawsynth 0.1 0.1 >> mul ~mod2
+~t2: saw 90 >> mul 0.2
+out: mix ~t.. >> mul ~mod2
+"#;
+
+        let (tree, rope) = parse(source_code);
+
+        let texts = |ranges: Vec<std::ops::Range<usize>>| {
+            ranges
+                .into_iter()
+                .map(|range| {
+                    (
+                        rope.byte_to_line(range.start),
+                        rope.byte_slice(range).to_string(),
+                    )
+                })
+                .collect::<Vec<_>>()
+        };
+
+        assert_eq!(
+            texts(references(&tree, rope.slice(..), 1, 2, true)),
+            vec![
+                (1, "~mod2".to_string()),
+                (2, "~mod2".to_string()),
+                (2, "~mod2".to_string()),
+                (4, "~mod2".to_string()),
+            ]
+        );
+
+        assert_eq!(
+            texts(references(&tree, rope.slice(..), 2, 1, false)),
+            vec![(4, "~t..".to_string())]
+        );
+
+        assert_eq!(
+            texts(references(&tree, rope.slice(..), 4, 10, true)),
+            vec![
+                (2, "~t1".to_string()),
+                (3, "~t2".to_string()),
+                (4, "~t..".to_string()),
+            ]
+        );
+    }
+}
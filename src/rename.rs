@@ -0,0 +1,145 @@
+use crate::syntax::{lines, matches, reference_at, reference_uses, Token};
+use ropey::RopeSlice;
+use std::ops::Range;
+use tree_sitter::Tree;
+
+/// The reference that would be renamed from the given position. Wildcards can't be renamed, and
+/// anything else that isn't a reference gives `Ok(None)`.
+pub fn prepare_rename(
+    tree: &Tree,
+    rope: RopeSlice,
+    line: usize,
+    col: usize,
+) -> Result<Option<Range<usize>>, String> {
+    Ok(symbol_at(tree, rope, line, col)?.map(|symbol| symbol.byte_range))
+}
+
+fn symbol_at(
+    tree: &Tree,
+    rope: RopeSlice,
+    line: usize,
+    col: usize,
+) -> Result<Option<Token>, String> {
+    match reference_at(tree, rope, line, col) {
+        Some(symbol) if symbol.text.ends_with("..") => Err(format!(
+            "`{}` is a wildcard and can't be renamed",
+            symbol.text
+        )),
+        symbol => Ok(symbol),
+    }
+}
+
+/// The ranges to replace with `new_name` to rename the reference at the given position: its
+/// labels and every use of it. Wildcards are left as they are, so the rename is refused if it
+/// would change what one of them matches.
+pub fn rename(
+    tree: &Tree,
+    rope: RopeSlice,
+    line: usize,
+    col: usize,
+    new_name: &str,
+) -> Result<Vec<Range<usize>>, String> {
+    let symbol = symbol_at(tree, rope, line, col)?
+        .ok_or_else(|| "there is no reference to rename here".to_string())?;
+
+    if !is_valid_name(new_name) {
+        return Err(format!(
+            "`{}` is not a valid reference, it should look like `~name`",
+            new_name
+        ));
+    }
+
+    let labels = lines(tree, rope)
+        .into_iter()
+        .map(|line| line.label)
+        .collect::<Vec<_>>();
+
+    if labels.iter().any(|label| label.text == new_name) {
+        return Err(format!("`{}` is already defined", new_name));
+    }
+
+    let uses = reference_uses(tree, rope);
+
+    // An undefined reference with the new name would start reading the renamed line.
+    if uses.iter().any(|reference| reference.text == new_name) {
+        return Err(format!("`{}` is already used", new_name));
+    }
+
+    let (wildcards, uses): (Vec<Token>, Vec<Token>) = uses
+        .into_iter()
+        .partition(|reference| reference.text.ends_with(".."));
+
+    let changes_matches = |wildcard: &&Token| {
+        matches(&wildcard.text, &symbol.text) != matches(&wildcard.text, new_name)
+    };
+
+    if let Some(wildcard) = wildcards.iter().find(changes_matches) {
+        return Err(format!(
+            "renaming `{}` to `{}` would change what `{}` matches",
+            symbol.text, new_name, wildcard.text
+        ));
+    }
+
+    let mut result = labels
+        .into_iter()
+        .chain(uses)
+        .filter(|token| token.text == symbol.text)
+        .map(|token| token.byte_range)
+        .collect::<Vec<_>>();
+
+    result.sort_by_key(|byte_range| byte_range.start);
+
+    Ok(result)
+}
+
+fn is_valid_name(name: &str) -> bool {
+    match name.strip_prefix('~') {
+        Some(ident) => {
+            !ident.is_empty() && ident.chars().all(|c| c.is_ascii_alphanumeric() || c == '_')
+        }
+        None => false,
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::rename;
+    use crate::helpers::parse;
+
+    #[test]
+    fn test_rename() {
+        let source_code = r#"
+~mod2: sin 0.3
+~t1: saw 60 >> mul ~mod2
+~b1: saw 90 >> mul ~mod2
+out: mix ~t.. ~b1 >> mul 0.5
+~c1: sin 1 >> mul ~pad
+"#;
+
+        let (tree, rope) = parse(source_code);
+
+        let lines = |ranges: Vec<std::ops::Range<usize>>| {
+            ranges
+                .into_iter()
+                .map(|range| rope.byte_to_line(range.start))
+                .collect::<Vec<_>>()
+        };
+
+        assert_eq!(
+            lines(rename(&tree, rope.slice(..), 1, 1, "~lfo").unwrap()),
+            vec![1, 2, 3]
+        );
+
+        assert_eq!(
+            lines(rename(&tree, rope.slice(..), 3, 1, "~bass").unwrap()),
+            vec![3, 4]
+        );
+
+        assert!(rename(&tree, rope.slice(..), 1, 1, "lfo").is_err());
+        assert!(rename(&tree, rope.slice(..), 1, 1, "~t..").is_err());
+        assert!(rename(&tree, rope.slice(..), 1, 1, "~b1").is_err());
+        assert!(rename(&tree, rope.slice(..), 2, 1, "~lead").is_err());
+        assert!(rename(&tree, rope.slice(..), 3, 1, "~t2").is_err());
+        assert!(rename(&tree, rope.slice(..), 1, 1, "~pad").is_err());
+    }
+}
@@ -0,0 +1,105 @@
+use crate::wav::Header;
+use std::collections::HashMap;
+use std::path::{Path, PathBuf};
+use walkdir::WalkDir;
+
+/// The `.wav` files found in the configured sample directories, indexed by file stem.
+///
+/// `\guitar` resolves to every `guitar.wav` found, in the order the directories were given.
+#[derive(Debug, Default)]
+pub struct SampleLibrary {
+    samples: HashMap<String, Vec<PathBuf>>,
+    /// The headers of the files that could be read.
+    headers: HashMap<PathBuf, Header>,
+}
+
+impl SampleLibrary {
+    pub fn index(directories: &[PathBuf]) -> Self {
+        let mut samples = HashMap::<String, Vec<PathBuf>>::new();
+        let mut headers = HashMap::new();
+
+        for directory in directories {
+            let entries = WalkDir::new(directory)
+                .follow_links(true)
+                .sort_by_file_name()
+                .into_iter()
+                .filter_map(|entry| entry.ok());
+
+            for entry in entries {
+                let path = entry.path();
+
+                if !entry.file_type().is_file() || !is_wav(path) {
+                    continue;
+                }
+
+                if let Ok(header) = Header::open(path) {
+                    headers.insert(path.to_path_buf(), header);
+                }
+
+                if let Some(name) = path.file_stem().and_then(|stem| stem.to_str()) {
+                    samples
+                        .entry(name.to_string())
+                        .or_default()
+                        .push(path.to_path_buf());
+                }
+            }
+        }
+
+        Self { samples, headers }
+    }
+
+    /// Looks up a sample by name, with or without the leading `\`.
+    pub fn get(&self, name: &str) -> Option<&[PathBuf]> {
+        self.samples
+            .get(name.strip_prefix('\\').unwrap_or(name))
+            .map(Vec::as_slice)
+    }
+
+    pub fn contains(&self, name: &str) -> bool {
+        self.get(name).is_some()
+    }
+
+    /// No samples were found, most likely because no sample directories are configured.
+    pub fn is_empty(&self) -> bool {
+        self.samples.is_empty()
+    }
+
+    pub fn header(&self, path: &Path) -> Option<&Header> {
+        self.headers.get(path)
+    }
+
+    pub fn names(&self) -> impl Iterator<Item = &str> {
+        self.samples.keys().map(String::as_str)
+    }
+}
+
+fn is_wav(path: &Path) -> bool {
+    path.extension()
+        .and_then(|extension| extension.to_str())
+        .is_some_and(|extension| extension.eq_ignore_ascii_case("wav"))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::SampleLibrary;
+    use crate::helpers::TestDir;
+
+    #[test]
+    fn test_index() {
+        let dir = TestDir::new("index");
+
+        dir.write("a/drums/kick1.wav", []);
+        dir.write("a/guitar.WAV", []);
+        dir.write("a/notes.txt", []);
+        dir.write("b/kick1.wav", []);
+
+        let library = SampleLibrary::index(&[dir.join("a"), dir.join("b")]);
+
+        assert_eq!(
+            library.get("\\kick1").unwrap(),
+            &[dir.join("a/drums/kick1.wav"), dir.join("b/kick1.wav")]
+        );
+        assert!(library.contains("guitar"));
+        assert!(!library.contains("notes"));
+    }
+}
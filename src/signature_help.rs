@@ -0,0 +1,145 @@
+use crate::api::{is_variadic, Parameter, NODE_DOCS};
+use crate::helpers::position_to_byte;
+use crate::syntax::{lines, Call};
+use ropey::RopeSlice;
+use tower_lsp::lsp_types::{
+    Documentation, ParameterInformation, ParameterLabel, Position, SignatureHelp,
+    SignatureInformation,
+};
+use tree_sitter::Tree;
+
+/// The parameters of the node call the cursor is in, with the one being typed as active.
+pub fn signature_help(
+    tree: &Tree,
+    rope: RopeSlice,
+    line: usize,
+    col: usize,
+) -> Option<SignatureHelp> {
+    let byte = position_to_byte(rope, Position::new(line as u32, col as u32));
+
+    let call = call_at(tree, rope, byte)?;
+    let entry = NODE_DOCS.get(&call.name.text)?;
+    let parameters = entry.parameters();
+
+    let mut label = call.name.text.clone();
+
+    let parameter_information = parameters
+        .iter()
+        .map(|parameter| {
+            label.push(' ');
+
+            let start = label.len() as u32;
+
+            label.push_str(&parameter.name);
+
+            ParameterInformation {
+                label: ParameterLabel::LabelOffsets([start, label.len() as u32]),
+                documentation: Some(Documentation::String(parameter_documentation(parameter))),
+            }
+        })
+        .collect::<Vec<_>>();
+
+    // Typing at the end of an argument still counts as that argument.
+    let mut active = call
+        .arguments
+        .iter()
+        .filter(|argument| argument.byte_range.end < byte)
+        .count();
+
+    if is_variadic(&call.name.text, parameters) {
+        active = active.min(parameters.len().saturating_sub(1));
+    }
+
+    Some(SignatureHelp {
+        signatures: vec![SignatureInformation {
+            label,
+            documentation: entry
+                .summary()
+                .map(|summary| Documentation::String(summary.to_string())),
+            parameters: Some(parameter_information),
+            active_parameter: None,
+        }],
+        active_signature: Some(0),
+        active_parameter: Some(active as u32),
+    })
+}
+
+/// Like `a number from 0 to 1, 0.5 by default. The dry/wet balance.`
+fn parameter_documentation(parameter: &Parameter) -> String {
+    let mut result = parameter.type_description();
+
+    if let Some(default) = &parameter.default {
+        result.push_str(&format!(", {} by default", default));
+    }
+
+    if let Some(description) = &parameter.description {
+        result.push_str(&format!(". {}", description));
+    }
+
+    result
+}
+
+/// The call that starts before `byte` with nothing but its arguments in between, so that it's
+/// still found after a trailing space.
+fn call_at(tree: &Tree, rope: RopeSlice, byte: usize) -> Option<Call> {
+    lines(tree, rope)
+        .into_iter()
+        .flat_map(|line| line.calls)
+        .rev()
+        .find(|call| call.name.byte_range.start < byte)
+        .filter(|call| {
+            let end = call.byte_range().end;
+
+            end >= byte || {
+                let rest = rope.byte_slice(end..byte).to_string();
+
+                !rest.contains(">>") && !rest.contains(';') && !rest.contains('\n')
+            }
+        })
+        .filter(|call| !call.name.text.starts_with('~'))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::signature_help;
+    use crate::helpers::parse;
+    use tower_lsp::lsp_types::ParameterLabel;
+
+    #[test]
+    fn test_signature_help() {
+        let source_code = "\n~a: saw 60 >> lpf 300 \nout: ~a >>\n";
+
+        let (tree, rope) = parse(source_code);
+
+        let help = signature_help(&tree, rope.slice(..), 1, 22).unwrap();
+        let signature = &help.signatures[0];
+
+        assert!(signature.label.starts_with("lpf "));
+        assert_eq!(help.active_parameter, Some(1));
+
+        match signature.parameters.as_ref().unwrap()[0].label {
+            ParameterLabel::LabelOffsets([start, _]) => assert_eq!(start, 4),
+            ref label => panic!("unexpected label {:?}", label),
+        }
+
+        assert_eq!(
+            signature_help(&tree, rope.slice(..), 1, 21)
+                .unwrap()
+                .active_parameter,
+            Some(0)
+        );
+        assert_eq!(
+            signature_help(&tree, rope.slice(..), 1, 8)
+                .unwrap()
+                .signatures[0]
+                .label
+                .split(' ')
+                .next(),
+            Some("saw")
+        );
+        assert!(signature_help(&tree, rope.slice(..), 2, 10).is_none());
+
+        // Clients can send a position past the end of the line.
+        assert!(signature_help(&tree, rope.slice(..), 3, 40).is_none());
+    }
+}
@@ -0,0 +1,166 @@
+use crate::syntax::{ArgumentKind, Call, Line, Token};
+
+/// Below this, oscillators are taken as modulation rather than sound.
+const CONTROL_RATE_LIMIT: f64 = 20.0;
+
+const OSCILLATORS: &[(&str, &str)] = &[
+    ("sin", "sine"),
+    ("saw", "sawtooth"),
+    ("squ", "square"),
+    ("tri", "triangle"),
+];
+
+const SYNTHS: &[(&str, &str)] = &[
+    ("sinsynth", "sine synth"),
+    ("sawsynth", "sawtooth synth"),
+    ("squsynth", "square synth"),
+    ("trisynth", "triangle synth"),
+];
+
+const FILTERS: &[(&str, &str)] = &[
+    ("lpf", "low-pass filtered"),
+    ("hpf", "high-pass filtered"),
+    ("onepole", "one-pole filtered"),
+];
+
+/// Describes in a few words what a line produces, like `control-rate sine at 0.3 Hz`, one step
+/// per node of the chain.
+pub fn summarize(line: &Line) -> String {
+    line.calls
+        .iter()
+        .map(describe)
+        .collect::<Vec<_>>()
+        .join(" → ")
+}
+
+fn describe(call: &Call) -> String {
+    let name = call.name.text.as_str();
+    let first = call.arguments.first();
+    let arguments = || {
+        call.arguments
+            .iter()
+            .map(|argument| argument.text.as_str())
+            .collect::<Vec<_>>()
+            .join(" ")
+    };
+
+    if name.starts_with('~') {
+        return format!("`{}`", name);
+    }
+
+    if let Some(wave) = lookup(OSCILLATORS, name) {
+        return match first.map(number) {
+            Some(Some(frequency)) if frequency < CONTROL_RATE_LIMIT => {
+                format!("control-rate {} at {} Hz", wave, frequency)
+            }
+            Some(Some(frequency)) => format!("{} at {} Hz", wave, frequency),
+            _ => format!("{}{}", wave, frequency_from(first, "frequency")),
+        };
+    }
+
+    if let Some(synth) = lookup(SYNTHS, name) {
+        return synth.to_string();
+    }
+
+    if let Some(filter) = lookup(FILTERS, name) {
+        return format!("{}{}", filter, frequency_from(first, "cutoff"));
+    }
+
+    match (name, first) {
+        ("noise", _) => "white noise".to_string(),
+        ("imp", Some(argument)) => format!("impulses at {} Hz", argument.text),
+        ("seq", _) => "note sequence".to_string(),
+        ("speed", Some(argument)) => format!("clock at {}x speed", argument.text),
+        ("choose", _) => format!("random choice of {}", arguments()),
+        ("mix", _) => format!("mix of {}", arguments()),
+        ("sp", Some(argument)) => format!("playback of `{}`", argument.text),
+        ("mul", Some(argument)) if argument.argument_kind() == ArgumentKind::Reference => {
+            format!("modulated by `{}`", argument.text)
+        }
+        ("mul", Some(argument)) => format!("scaled by {}", argument.text),
+        ("add", Some(argument)) => format!("offset by {}", argument.text),
+        ("pan", Some(argument)) => format!("panned to {}", argument.text),
+        ("plate", _) => "plate reverb".to_string(),
+        (name, _) if name.starts_with("delay") => "delayed".to_string(),
+        (name, _) => name.to_string(),
+    }
+}
+
+/// ` at 400 Hz` or ` with its cutoff from ~lfo`, from the first argument.
+fn frequency_from(argument: Option<&Token>, what: &str) -> String {
+    match argument {
+        Some(argument) if argument.argument_kind() == ArgumentKind::Reference => {
+            format!(" with its {} from `{}`", what, argument.text)
+        }
+        Some(argument) => match number(argument) {
+            Some(frequency) => format!(" at {} Hz", frequency),
+            None => String::new(),
+        },
+        None => String::new(),
+    }
+}
+
+fn number(token: &Token) -> Option<f64> {
+    token.text.parse().ok()
+}
+
+fn lookup(table: &[(&str, &'static str)], name: &str) -> Option<&'static str> {
+    table
+        .iter()
+        .find(|(node, _)| *node == name)
+        .map(|(_, description)| *description)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::summarize;
+    use crate::syntax::{Call, Line, Token};
+
+    fn token(text: &str) -> Token {
+        Token {
+            text: text.to_string(),
+            byte_range: 0..0,
+        }
+    }
+
+    fn line(label: &str, calls: &[&[&str]]) -> Line {
+        Line {
+            label: token(label),
+            calls: calls
+                .iter()
+                .map(|call| Call {
+                    name: token(call[0]),
+                    arguments: call[1..].iter().map(|argument| token(argument)).collect(),
+                })
+                .collect(),
+            byte_range: 0..0,
+            has_error: false,
+        }
+    }
+
+    #[test]
+    fn test_summarize() {
+        assert_eq!(
+            summarize(&line("~mod2", &[&["sin", "0.3"]])),
+            "control-rate sine at 0.3 Hz"
+        );
+        assert_eq!(
+            summarize(&line(
+                "~u2",
+                &[
+                    &["speed", "4.0"],
+                    &["seq", "60", "64"],
+                    &["squsynth", "0.5", "0.1"],
+                    &["mul", "~mod2"],
+                    &["lpf", "400.0", "1.0"]
+                ]
+            )),
+            "clock at 4.0x speed → note sequence → square synth → modulated by `~mod2` → \
+             low-pass filtered at 400 Hz"
+        );
+        assert_eq!(
+            summarize(&line("out", &[&["~u2"], &["hpf", "~lfo", "1.0"]])),
+            "`~u2` → high-pass filtered with its cutoff from `~lfo`"
+        );
+    }
+}
@@ -0,0 +1,392 @@
+use crate::helpers::position_to_byte;
+use ropey::RopeSlice;
+use std::ops::Range;
+use tower_lsp::lsp_types::Position;
+use tree_sitter::{Node, Tree};
+
+/// A piece of source text like `~t1`, `0.25` or `\guitar`.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Token {
+    pub text: String,
+    pub byte_range: Range<usize>,
+}
+
+/// A `label: chain` line.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Line {
+    pub label: Token,
+    pub calls: Vec<Call>,
+    pub byte_range: Range<usize>,
+    /// Whether the tree found a syntax error somewhere in the line.
+    pub has_error: bool,
+}
+
+/// A node of the chain, like `lpf 300 1`. Chains can also start with a reference, in which
+/// case `name` is the `~name`.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Call {
+    pub name: Token,
+    pub arguments: Vec<Token>,
+}
+
+impl Call {
+    pub fn byte_range(&self) -> Range<usize> {
+        let end = self
+            .arguments
+            .last()
+            .map_or(self.name.byte_range.end, |argument| argument.byte_range.end);
+
+        self.name.byte_range.start..end
+    }
+}
+
+/// What an argument looks like, judging by its text.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum ArgumentKind {
+    Number,
+    Reference,
+    Sample,
+    Other,
+}
+
+impl Token {
+    pub fn argument_kind(&self) -> ArgumentKind {
+        if self.text.starts_with('~') {
+            ArgumentKind::Reference
+        } else if self.text.starts_with('\\') {
+            ArgumentKind::Sample
+        } else if self.text.parse::<f64>().is_ok() {
+            ArgumentKind::Number
+        } else {
+            ArgumentKind::Other
+        }
+    }
+}
+
+const PUNCTUATION: &[&str] = &[":", ";", ">>"];
+
+/// Collects the tokens under `node`, skipping comments.
+///
+/// Leaves that touch each other are merged, so things like `\guitar` or `_~c` come out as a
+/// single token no matter how the grammar splits them.
+pub fn tokens(node: Node, rope: RopeSlice) -> Vec<Token> {
+    let mut leaves = vec![];
+    collect_leaves(node, &mut leaves);
+
+    let mut tokens: Vec<Token> = vec![];
+    let mut previous_is_punctuation = true;
+
+    for leaf in leaves {
+        let byte_range = leaf.byte_range();
+
+        if byte_range.is_empty() {
+            continue;
+        }
+
+        let text = rope.byte_slice(byte_range.clone()).to_string();
+
+        if text.starts_with("//") {
+            previous_is_punctuation = true;
+            continue;
+        }
+
+        let is_punctuation = PUNCTUATION.contains(&text.as_str());
+
+        match tokens.last_mut() {
+            Some(last)
+                if !is_punctuation
+                    && !previous_is_punctuation
+                    && last.byte_range.end == byte_range.start =>
+            {
+                last.text.push_str(&text);
+                last.byte_range.end = byte_range.end;
+            }
+            _ => tokens.push(Token { text, byte_range }),
+        }
+
+        previous_is_punctuation = is_punctuation;
+    }
+
+    tokens
+}
+
+fn collect_leaves<'tree>(node: Node<'tree>, leaves: &mut Vec<Node<'tree>>) {
+    if node.child_count() == 0 {
+        leaves.push(node);
+        return;
+    }
+
+    let mut cursor = node.walk();
+
+    for child in node.children(&mut cursor) {
+        collect_leaves(child, leaves);
+    }
+}
+
+/// Every line in the document, in source order.
+pub fn lines(tree: &Tree, rope: RopeSlice) -> Vec<Line> {
+    let root_node = tree.root_node();
+    let mut cursor = root_node.walk();
+
+    let mut lines = vec![];
+
+    for line in root_node.children(&mut cursor) {
+        if line.kind() != "line" {
+            continue;
+        }
+
+        let mut tokens = tokens(line, rope).into_iter();
+
+        let label = match tokens.next() {
+            Some(label) => label,
+            None => continue,
+        };
+
+        let mut calls = vec![];
+        let mut segment = vec![];
+
+        for token in tokens.chain(std::iter::once(Token {
+            text: ">>".to_string(),
+            byte_range: line.end_byte()..line.end_byte(),
+        })) {
+            match token.text.as_str() {
+                ":" | ";" => {}
+                ">>" => {
+                    let mut arguments = std::mem::take(&mut segment).into_iter();
+
+                    if let Some(name) = arguments.next() {
+                        calls.push(Call {
+                            name,
+                            arguments: arguments.collect(),
+                        });
+                    }
+                }
+                _ => segment.push(token),
+            }
+        }
+
+        lines.push(Line {
+            label,
+            calls,
+            byte_range: line.byte_range(),
+            has_error: line.has_error(),
+        });
+    }
+
+    lines
+}
+
+/// The tokens of every line, leaving out the labels.
+pub fn line_tokens(tree: &Tree, rope: RopeSlice) -> Vec<Token> {
+    let root_node = tree.root_node();
+    let mut cursor = root_node.walk();
+
+    let mut result = vec![];
+
+    for line in root_node.children(&mut cursor) {
+        if line.kind() != "line" {
+            continue;
+        }
+
+        let label_end = line
+            .child(0)
+            .map_or(line.start_byte(), |label| label.end_byte());
+
+        result.extend(
+            tokens(line, rope)
+                .into_iter()
+                .filter(|token| token.byte_range.start >= label_end),
+        );
+    }
+
+    result
+}
+
+/// Every `~name` that is used (not defined) in the document, in source order.
+pub fn reference_uses(tree: &Tree, rope: RopeSlice) -> Vec<Token> {
+    line_tokens(tree, rope)
+        .iter()
+        .flat_map(references)
+        .collect()
+}
+
+/// Every `\sample` in the document, in source order.
+pub fn sample_uses(tree: &Tree, rope: RopeSlice) -> Vec<Token> {
+    line_tokens(tree, rope)
+        .into_iter()
+        .filter(|token| token.text.starts_with('\\'))
+        .collect()
+}
+
+/// The label or reference at the given position, if any. The position right after the token
+/// counts too, so it works with the cursor at the end of a word.
+pub fn reference_at(tree: &Tree, rope: RopeSlice, line: usize, col: usize) -> Option<Token> {
+    let byte = position_to_byte(rope, Position::new(line as u32, col as u32));
+
+    lines(tree, rope)
+        .into_iter()
+        .map(|line| line.label)
+        .filter(|label| label.text.starts_with('~'))
+        .chain(reference_uses(tree, rope))
+        .find(|token| token.byte_range.start <= byte && byte <= token.byte_range.end)
+}
+
+/// The `\sample` at the given position, if any, with the same rules as [`reference_at`].
+pub fn sample_at(tree: &Tree, rope: RopeSlice, line: usize, col: usize) -> Option<Token> {
+    let byte = position_to_byte(rope, Position::new(line as u32, col as u32));
+
+    sample_uses(tree, rope)
+        .into_iter()
+        .find(|token| token.byte_range.start <= byte && byte <= token.byte_range.end)
+}
+
+/// An argument found by position, with the line it's in so the rest of the chain can be looked
+/// at.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct ArgumentAt {
+    pub line: Line,
+    /// The index of the call in the chain.
+    pub call: usize,
+    /// The index of the argument in the call.
+    pub argument: usize,
+}
+
+impl ArgumentAt {
+    pub fn call(&self) -> &Call {
+        &self.line.calls[self.call]
+    }
+
+    pub fn token(&self) -> &Token {
+        &self.call().arguments[self.argument]
+    }
+}
+
+/// The argument of a node call at the given position, if any.
+pub fn argument_at(tree: &Tree, rope: RopeSlice, line: usize, col: usize) -> Option<ArgumentAt> {
+    let byte = position_to_byte(rope, Position::new(line as u32, col as u32));
+
+    lines(tree, rope).into_iter().find_map(|line| {
+        let (call, argument) = line.calls.iter().enumerate().find_map(|(i, call)| {
+            call.arguments
+                .iter()
+                .position(|argument| {
+                    argument.byte_range.start <= byte && byte <= argument.byte_range.end
+                })
+                .map(|argument| (i, argument))
+        })?;
+
+        Some(ArgumentAt {
+            line,
+            call,
+            argument,
+        })
+    })
+}
+
+/// Splits the `~name` parts out of a token, e.g. `_~c` yields `~c`.
+pub fn references(token: &Token) -> Vec<Token> {
+    let mut references = vec![];
+    let mut chars = token.text.char_indices().peekable();
+
+    while let Some((start, c)) = chars.next() {
+        if c != '~' {
+            continue;
+        }
+
+        let mut end = start + c.len_utf8();
+
+        while let Some(&(i, c)) = chars.peek() {
+            if !(c.is_alphanumeric() || c == '_' || c == '.') {
+                break;
+            }
+
+            end = i + c.len_utf8();
+            chars.next();
+        }
+
+        if end > start + 1 {
+            references.push(Token {
+                text: token.text[start..end].to_string(),
+                byte_range: token.byte_range.start + start..token.byte_range.start + end,
+            });
+        }
+    }
+
+    references
+}
+
+/// Whether `pattern` refers to `label`, where patterns like `~t..` match every label with that
+/// prefix.
+pub fn matches(pattern: &str, label: &str) -> bool {
+    match pattern.strip_suffix("..") {
+        Some(prefix) => label.starts_with(prefix),
+        None => pattern == label,
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::{lines, matches, reference_uses};
+    use crate::helpers::parse;
+
+    #[test]
+    fn test_reference_uses() {
+        let source_code = r#"
+~a: choose 70 71
+~t1: speed 0.25 >> seq ~a _ _ 69 >> sp \guitar >> mul ~mod
+out: mix ~t.. >> mul 1
+"#;
+
+        let (tree, rope) = parse(source_code);
+
+        assert_eq!(
+            reference_uses(&tree, rope.slice(..))
+                .into_iter()
+                .map(|token| token.text)
+                .collect::<Vec<_>>(),
+            vec!["~a", "~mod", "~t.."]
+        );
+    }
+
+    #[test]
+    fn test_lines() {
+        let source_code = r#"
+~t1: speed 0.25 >> seq 60 _ _ 69 >> sp \guitar
+out: ~t1 >> mul 0.5;
+"#;
+
+        let (tree, rope) = parse(source_code);
+
+        let lines = lines(&tree, rope.slice(..));
+
+        assert_eq!(lines.len(), 2);
+        assert_eq!(lines[0].label.text, "~t1");
+        assert_eq!(lines[1].label.text, "out");
+
+        assert_eq!(
+            lines[0]
+                .calls
+                .iter()
+                .map(|call| {
+                    std::iter::once(&call.name)
+                        .chain(&call.arguments)
+                        .map(|token| token.text.as_str())
+                        .collect::<Vec<_>>()
+                        .join(" ")
+                })
+                .collect::<Vec<_>>(),
+            vec!["speed 0.25", "seq 60 _ _ 69", "sp \\guitar"]
+        );
+
+        assert_eq!(lines[1].calls[0].name.text, "~t1");
+        assert!(lines[1].calls[0].arguments.is_empty());
+    }
+
+    #[test]
+    fn test_matches() {
+        assert!(matches("~t..", "~t1"));
+        assert!(matches("~t1", "~t1"));
+        assert!(!matches("~t..", "~b1"));
+        assert!(!matches("~t1", "~t10"));
+    }
+}
@@ -0,0 +1,258 @@
+use std::fs::File;
+use std::io::{self, BufReader, Read, Seek, SeekFrom};
+use std::path::Path;
+use std::time::Duration;
+
+const FORMAT_PCM: u16 = 1;
+const FORMAT_FLOAT: u16 = 3;
+const FORMAT_EXTENSIBLE: u16 = 0xFFFE;
+/// The size of an extensible `fmt ` chunk, the longest one that is parsed.
+const FMT_LEN: usize = 40;
+/// Anything bigger than this is not a `fmt ` chunk, even with some extra bytes.
+const MAX_FMT_SIZE: u32 = 1024;
+
+/// What the `fmt ` and `data` chunks of a `.wav` file tell about its audio.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct Header {
+    /// 1 for integer PCM, 3 for floats.
+    pub format: u16,
+    pub channels: u16,
+    pub sample_rate: u32,
+    pub bits_per_sample: u16,
+    /// Where the samples start in the file.
+    pub data_offset: u64,
+    /// The size of the samples in bytes.
+    pub data_len: u32,
+}
+
+impl Header {
+    pub fn open(path: &Path) -> io::Result<Self> {
+        Self::read(BufReader::new(File::open(path)?))
+    }
+
+    /// Reads the chunks up to `data`, skipping the ones it doesn't need.
+    pub fn read(mut reader: impl Read + Seek) -> io::Result<Self> {
+        let mut riff = [0; 12];
+        reader.read_exact(&mut riff)?;
+
+        if &riff[0..4] != b"RIFF" || &riff[8..12] != b"WAVE" {
+            return Err(invalid("not a RIFF WAVE file"));
+        }
+
+        let mut format = None;
+
+        loop {
+            let mut chunk = [0; 8];
+            reader.read_exact(&mut chunk)?;
+
+            let size = u32::from_le_bytes([chunk[4], chunk[5], chunk[6], chunk[7]]);
+            // Chunks are padded to an even size.
+            let padded = u64::from(size) + u64::from(size % 2);
+
+            match &chunk[0..4] {
+                b"fmt " => {
+                    if size > MAX_FMT_SIZE {
+                        return Err(invalid("the fmt chunk is too long"));
+                    }
+
+                    // The size comes from the file, so only the bytes that are parsed are read.
+                    let len = (size as usize).min(FMT_LEN);
+                    let mut fmt = [0; FMT_LEN];
+                    reader.read_exact(&mut fmt[..len])?;
+                    reader.seek(SeekFrom::Current(padded as i64 - len as i64))?;
+
+                    format = Some(parse_format(&fmt[..len])?);
+                }
+                b"data" => {
+                    let (format, channels, sample_rate, bits_per_sample) =
+                        format.ok_or_else(|| invalid("the data chunk comes before fmt"))?;
+
+                    return Ok(Self {
+                        format,
+                        channels,
+                        sample_rate,
+                        bits_per_sample,
+                        data_offset: reader.stream_position()?,
+                        data_len: size,
+                    });
+                }
+                _ => {
+                    reader.seek(SeekFrom::Current(padded as i64))?;
+                }
+            }
+        }
+    }
+
+    /// The number of samples per channel.
+    pub fn frames(&self) -> u64 {
+        let frame_size = u64::from(self.channels) * u64::from(self.bits_per_sample / 8);
+
+        u64::from(self.data_len)
+            .checked_div(frame_size)
+            .unwrap_or(0)
+    }
+
+    pub fn duration(&self) -> Duration {
+        if self.sample_rate == 0 {
+            Duration::ZERO
+        } else {
+            Duration::from_secs_f64(self.frames() as f64 / f64::from(self.sample_rate))
+        }
+    }
+
+    /// The highest absolute sample value of the file, from 0 to 1 for full scale.
+    pub fn peak(&self, path: &Path) -> io::Result<f32> {
+        let mut reader = BufReader::new(File::open(path)?);
+
+        reader.seek(SeekFrom::Start(self.data_offset))?;
+
+        self.read_peak(reader.take(u64::from(self.data_len)))
+    }
+
+    fn read_peak(&self, mut reader: impl Read) -> io::Result<f32> {
+        let decode: fn(&[u8]) -> f32 = match (self.format, self.bits_per_sample) {
+            (FORMAT_PCM, 8) => |bytes| (f32::from(bytes[0]) - 128.0) / 128.0,
+            (FORMAT_PCM, 16) => {
+                |bytes| f32::from(i16::from_le_bytes([bytes[0], bytes[1]])) / 32768.0
+            }
+            (FORMAT_PCM, 24) => |bytes| {
+                (i32::from_le_bytes([0, bytes[0], bytes[1], bytes[2]]) >> 8) as f32 / 8388608.0
+            },
+            (FORMAT_PCM, 32) => |bytes| {
+                i32::from_le_bytes([bytes[0], bytes[1], bytes[2], bytes[3]]) as f32 / 2147483648.0
+            },
+            (FORMAT_FLOAT, 32) => {
+                |bytes| f32::from_le_bytes([bytes[0], bytes[1], bytes[2], bytes[3]])
+            }
+            (FORMAT_FLOAT, 64) => {
+                |bytes| f64::from_le_bytes(bytes[0..8].try_into().unwrap()) as f32
+            }
+            _ => return Err(invalid("unsupported sample format")),
+        };
+
+        let size = usize::from(self.bits_per_sample / 8);
+        let mut buffer = vec![0; size * 4096];
+        let mut peak = 0f32;
+        let mut pending = 0;
+
+        loop {
+            let read = reader.read(&mut buffer[pending..])?;
+
+            if read == 0 {
+                return Ok(peak);
+            }
+
+            let available = pending + read;
+            let complete = available - available % size;
+
+            for bytes in buffer[..complete].chunks_exact(size) {
+                peak = peak.max(decode(bytes).abs());
+            }
+
+            // Keep a sample that was cut in half for the next read.
+            buffer.copy_within(complete..available, 0);
+            pending = available - complete;
+        }
+    }
+
+    /// Like `1.50 s, stereo`.
+    pub fn summary(&self) -> String {
+        let channels = match self.channels {
+            1 => "mono".to_string(),
+            2 => "stereo".to_string(),
+            channels => format!("{} channels", channels),
+        };
+
+        format!("{:.2} s, {}", self.duration().as_secs_f64(), channels)
+    }
+}
+
+fn parse_format(fmt: &[u8]) -> io::Result<(u16, u16, u32, u16)> {
+    if fmt.len() < 16 {
+        return Err(invalid("the fmt chunk is too short"));
+    }
+
+    let u16_at = |i: usize| u16::from_le_bytes([fmt[i], fmt[i + 1]]);
+
+    let mut format = u16_at(0);
+    let channels = u16_at(2);
+    let sample_rate = u32::from_le_bytes([fmt[4], fmt[5], fmt[6], fmt[7]]);
+    let bits_per_sample = u16_at(14);
+
+    // The actual format is at the start of the sub-format GUID.
+    if format == FORMAT_EXTENSIBLE && fmt.len() >= 26 {
+        format = u16_at(24);
+    }
+
+    Ok((format, channels, sample_rate, bits_per_sample))
+}
+
+fn invalid(message: &str) -> io::Error {
+    io::Error::new(io::ErrorKind::InvalidData, message)
+}
+
+/// A 16-bit PCM `.wav` file with the given samples, interleaved when there are several channels.
+#[cfg(test)]
+pub fn fixture(channels: u16, sample_rate: u32, samples: &[i16]) -> Vec<u8> {
+    let data_len = (samples.len() * 2) as u32;
+
+    let mut file = vec![];
+
+    file.extend(b"RIFF");
+    file.extend((36 + data_len).to_le_bytes());
+    file.extend(b"WAVE");
+
+    file.extend(b"fmt ");
+    file.extend(16u32.to_le_bytes());
+    file.extend(FORMAT_PCM.to_le_bytes());
+    file.extend(channels.to_le_bytes());
+    file.extend(sample_rate.to_le_bytes());
+    file.extend((sample_rate * u32::from(channels) * 2).to_le_bytes());
+    file.extend((channels * 2).to_le_bytes());
+    file.extend(16u16.to_le_bytes());
+
+    file.extend(b"data");
+    file.extend(data_len.to_le_bytes());
+    file.extend(samples.iter().flat_map(|sample| sample.to_le_bytes()));
+
+    file
+}
+
+#[cfg(test)]
+mod tests {
+    use super::{fixture, Header};
+    use std::io::Cursor;
+    use std::time::Duration;
+
+    #[test]
+    fn test_header() {
+        let wav = fixture(2, 8000, &[0; 8000]);
+
+        // A chunk with an odd size before `fmt `, to be skipped.
+        let file = [&wav[..12], &b"LIST\x03\0\0\0\0\0\0\0"[..], &wav[12..]].concat();
+
+        let header = Header::read(Cursor::new(file)).unwrap();
+
+        assert_eq!(header.format, 1);
+        assert_eq!(header.channels, 2);
+        assert_eq!(header.sample_rate, 8000);
+        assert_eq!(header.data_offset, 56);
+        assert_eq!(header.frames(), 4000);
+        assert_eq!(header.duration(), Duration::from_millis(500));
+        assert_eq!(header.summary(), "0.50 s, stereo");
+
+        assert!(Header::read(Cursor::new(b"RIFF\0\0\0\0AVI ".to_vec())).is_err());
+
+        let mut huge = b"RIFF\0\0\0\0WAVEfmt ".to_vec();
+        huge.extend(u32::MAX.to_le_bytes());
+
+        assert!(Header::read(Cursor::new(huge)).is_err());
+
+        let samples = [0i16, 16384, -32768, 100]
+            .iter()
+            .flat_map(|sample| sample.to_le_bytes())
+            .collect::<Vec<_>>();
+
+        assert_eq!(header.read_peak(Cursor::new(samples)).unwrap(), 1.0);
+    }
+}
@@ -0,0 +1,288 @@
+use crate::helpers::byte_range_to_range;
+use crate::syntax::{lines, Line};
+use ropey::{Rope, RopeSlice};
+use std::collections::HashMap;
+use std::fs;
+use std::path::{Path, PathBuf};
+use tower_lsp::lsp_types::{Location, Range, SymbolInformation, SymbolKind, Url};
+use tree_sitter::{Parser, Tree};
+use walkdir::WalkDir;
+
+/// A line of some `.glicol` file in the workspace, which may not be open in the editor.
+#[derive(Debug, Clone, PartialEq)]
+pub struct IndexedLabel {
+    pub name: String,
+    pub range: Range,
+    pub selection_range: Range,
+    /// The chain of the line, like `saw 60 >> mul ~mod`.
+    pub chain: String,
+}
+
+/// The labels of every `.glicol` file under the workspace folders.
+///
+/// Open documents are indexed from the editor's contents, the rest from disk.
+#[derive(Debug, Default)]
+pub struct WorkspaceIndex {
+    pub folders: Vec<PathBuf>,
+    files: HashMap<Url, Vec<IndexedLabel>>,
+}
+
+impl WorkspaceIndex {
+    pub fn new(folders: Vec<PathBuf>) -> Self {
+        Self {
+            folders,
+            files: HashMap::new(),
+        }
+    }
+
+    /// Reads and parses every `.glicol` file under `folders`.
+    pub fn scan(folders: &[PathBuf]) -> HashMap<Url, Vec<IndexedLabel>> {
+        let mut parser = new_parser();
+        let mut files = HashMap::new();
+
+        for folder in folders {
+            let entries = WalkDir::new(folder)
+                .follow_links(true)
+                .into_iter()
+                .filter_map(|entry| entry.ok());
+
+            for entry in entries {
+                let path = entry.path();
+
+                if !entry.file_type().is_file() || !is_glicol(path) {
+                    continue;
+                }
+
+                if let (Ok(uri), Some(labels)) =
+                    (Url::from_file_path(path), read_labels(&mut parser, path))
+                {
+                    files.insert(uri, labels);
+                }
+            }
+        }
+
+        files
+    }
+
+    pub fn update(&mut self, uri: Url, labels: Vec<IndexedLabel>) {
+        self.files.insert(uri, labels);
+    }
+
+    pub fn remove(&mut self, uri: &Url) {
+        self.files.remove(uri);
+    }
+
+    /// Reads and parses some files again, with `None` for the ones that are gone or are not
+    /// `.glicol` files.
+    pub fn read(uris: Vec<Url>) -> Vec<(Url, Option<Vec<IndexedLabel>>)> {
+        let mut parser = new_parser();
+
+        uris.into_iter()
+            .map(|uri| {
+                let labels = uri
+                    .to_file_path()
+                    .ok()
+                    .filter(|path| is_glicol(path))
+                    .and_then(|path| read_labels(&mut parser, &path));
+
+                (uri, labels)
+            })
+            .collect()
+    }
+
+    /// Stores the labels [`WorkspaceIndex::read`] found for a file, or forgets it if it's gone or
+    /// outside the workspace.
+    pub fn reload(&mut self, uri: Url, labels: Option<Vec<IndexedLabel>>) {
+        let inside = uri.to_file_path().is_ok_and(|path| self.contains(&path));
+
+        match labels.filter(|_| inside) {
+            Some(labels) => self.update(uri, labels),
+            None => self.remove(&uri),
+        }
+    }
+
+    /// Forgets the files that are not under any of the workspace folders anymore.
+    pub fn retain_folders(&mut self) {
+        let folders = &self.folders;
+
+        self.files.retain(|uri, _| {
+            uri.to_file_path()
+                .is_ok_and(|path| folders.iter().any(|folder| path.starts_with(folder)))
+        });
+    }
+
+    pub fn contains(&self, path: &Path) -> bool {
+        self.folders.iter().any(|folder| path.starts_with(folder))
+    }
+
+    pub fn labels(&self) -> impl Iterator<Item = (&Url, &IndexedLabel)> {
+        self.files
+            .iter()
+            .flat_map(|(uri, labels)| labels.iter().map(move |label| (uri, label)))
+    }
+
+    /// The labels whose name contains the characters of `query` in order, best matches first.
+    pub fn symbols(&self, query: &str) -> Vec<SymbolInformation> {
+        let mut matches = self
+            .labels()
+            .filter_map(|(uri, label)| Some((fuzzy_match(query, &label.name)?, uri, label)))
+            .collect::<Vec<_>>();
+
+        matches.sort_by(|(a, a_uri, a_label), (b, b_uri, b_label)| {
+            a.cmp(b)
+                .then_with(|| a_label.name.cmp(&b_label.name))
+                .then_with(|| a_uri.as_str().cmp(b_uri.as_str()))
+                .then_with(|| a_label.range.start.line.cmp(&b_label.range.start.line))
+        });
+
+        matches
+            .into_iter()
+            .map(|(_, uri, label)| {
+                let kind = if label.name.starts_with('~') {
+                    SymbolKind::VARIABLE
+                } else {
+                    SymbolKind::EVENT
+                };
+
+                #[allow(deprecated)]
+                SymbolInformation {
+                    name: label.name.clone(),
+                    kind,
+                    tags: None,
+                    deprecated: None,
+                    location: Location {
+                        uri: uri.clone(),
+                        range: label.range,
+                    },
+                    container_name: file_name(uri),
+                }
+            })
+            .collect()
+    }
+}
+
+/// The file name of a document, like `intro.glicol`, to tell apart labels of different files.
+fn file_name(uri: &Url) -> Option<String> {
+    let path = uri.to_file_path().ok()?;
+
+    Some(path.file_name()?.to_string_lossy().into_owned())
+}
+
+/// The labels of a parsed document, to put in the index.
+pub fn labels(tree: &Tree, rope: RopeSlice) -> Vec<IndexedLabel> {
+    lines(tree, rope)
+        .into_iter()
+        .map(|line| IndexedLabel {
+            chain: chain(&line),
+            range: byte_range_to_range(rope, line.byte_range),
+            selection_range: byte_range_to_range(rope, line.label.byte_range),
+            name: line.label.text,
+        })
+        .collect()
+}
+
+fn chain(line: &Line) -> String {
+    line.calls
+        .iter()
+        .map(|call| {
+            std::iter::once(&call.name)
+                .chain(&call.arguments)
+                .map(|token| token.text.as_str())
+                .collect::<Vec<_>>()
+                .join(" ")
+        })
+        .collect::<Vec<_>>()
+        .join(" >> ")
+}
+
+fn new_parser() -> Parser {
+    let mut parser = Parser::new();
+
+    parser
+        .set_language(tree_sitter_glicol::language())
+        .expect("Error loading grammar");
+
+    parser
+}
+
+fn read_labels(parser: &mut Parser, path: &Path) -> Option<Vec<IndexedLabel>> {
+    let text = fs::read_to_string(path).ok()?;
+    let tree = parser.parse(&text, None)?;
+    let rope = Rope::from_str(&text);
+
+    Some(labels(&tree, rope.slice(..)))
+}
+
+pub fn is_glicol(path: &Path) -> bool {
+    path.extension()
+        .and_then(|extension| extension.to_str())
+        .is_some_and(|extension| extension == "glicol")
+}
+
+/// How far apart the characters of `query` are in `name`, ignoring case and the leading `~`, or
+/// `None` if they are not all in it. Lower is better.
+fn fuzzy_match(query: &str, name: &str) -> Option<usize> {
+    let query = query.strip_prefix('~').unwrap_or(query).to_lowercase();
+    let name = name.strip_prefix('~').unwrap_or(name).to_lowercase();
+
+    let mut score = 0;
+    let mut last = None;
+    let mut chars = name.chars().enumerate();
+
+    for wanted in query.chars() {
+        let (index, _) = chars.find(|(_, c)| *c == wanted)?;
+
+        score += match last {
+            Some(last) => index - last - 1,
+            None => index,
+        };
+
+        last = Some(index);
+    }
+
+    Some(score)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::WorkspaceIndex;
+    use crate::helpers::TestDir;
+    use tower_lsp::lsp_types::Url;
+
+    #[test]
+    fn test_workspace_symbols() {
+        let dir = TestDir::new("workspace");
+
+        dir.write(
+            "sets/intro.glicol",
+            "~bassline: saw 40 >> lpf 300 1\nout: ~bassline >> mul 0.5\n",
+        );
+        dir.write("sets/outro.glicol", "~bass: sin 55\n");
+        dir.write("notes.txt", "~bassline: sin 1\n");
+
+        let mut index = WorkspaceIndex::new(vec![dir.root.clone()]);
+
+        for (uri, labels) in WorkspaceIndex::scan(&index.folders.clone()) {
+            index.update(uri, labels);
+        }
+
+        let symbols = index.symbols("bsln");
+
+        assert_eq!(symbols.len(), 1);
+        assert_eq!(symbols[0].name, "~bassline");
+        assert_eq!(
+            symbols[0].location.uri,
+            Url::from_file_path(dir.join("sets/intro.glicol")).unwrap()
+        );
+        assert_eq!(symbols[0].container_name.as_deref(), Some("intro.glicol"));
+
+        assert_eq!(
+            index
+                .symbols("~bass")
+                .iter()
+                .map(|symbol| symbol.name.as_str())
+                .collect::<Vec<_>>(),
+            vec!["~bass", "~bassline"]
+        );
+    }
+}
@@ -59,7 +59,9 @@ export async function activate(context: ExtensionContext) {
 		synchronize: {
 			// Notify the server about file changes to '.clientrc files contained in the workspace
 			fileEvents: workspace.createFileSystemWatcher("**/.clientrc"),
+			configurationSection: "glicol-language-server",
 		},
+		initializationOptions: workspace.getConfiguration("glicol-language-server"),
 		traceOutputChannel,
 	};
 